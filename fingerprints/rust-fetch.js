@@ -1,5 +1,6 @@
 const { Client } = require('../dist');
 
+// Usage: node fingerprints/rust-fetch.js [url]
 (async () => {
     let client = new Client({
         connectTimeout: 5,
@@ -8,5 +9,7 @@ const { Client } = require('../dist');
         httpsOnly: true,
     });
 
-    let ret = await client.request('https://localhost:5000');
+    let ret = await client.request(process.argv[2] || 'https://localhost:5000');
+
+    console.log(ret.httpVersion, ret.statusCode);
 })();
@@ -1,23 +1,160 @@
+import { readFileSync } from 'fs'
+import { networkInterfaces } from 'os'
 import { promisify } from 'util'
 import * as FormData from 'form-data'
 import { CookieJar } from 'tough-cookie'
+import { PacResolver } from './pac'
 
 const {
+  tlsBackend: rfTlsBackend,
+
   clientRequest,
+  clientSetPacProxy,
+  clientPreconnect,
+  clientResetConnections,
+  clientConnections,
+  clientCloseIdleConnections,
+  clientClose,
+  clientStats,
+
+  dnsLookupComplete,
 
   builderNew,
   builderConnectTimeout,
   builderRequestTimeout,
+  builderWatchdogTimeout,
+  builderConcurrencyLimits,
+  builderRateLimit,
   builderRedirectLimit,
   builderHttpsOnly,
   builderStripSensitiveHeaders,
   builderHttps2AdaptiveWindow,
+  builderHttp2InitialStreamWindowSize,
+  builderHttp2InitialConnectionWindowSize,
+  builderHttp2MaxFrameSize,
+  builderPoolMaxIdlePerHost,
+  builderPoolIdleTimeout,
+  builderTcpKeepalive,
+  builderTcpNodelay,
+  builderHttp2KeepAliveInterval,
+  builderHttp2KeepAliveTimeout,
+  builderHttp2KeepAliveWhileIdle,
+  builderMinTlsVersion,
+  builderMaxTlsVersion,
+  builderHttp1Only,
+  builderHttp2PriorKnowledge,
+  builderAlpnProtocols,
+  builderTlsCipherSuites,
+  builderTlsKeyExchangeGroups,
+  builderTlsSignatureAlgorithms,
   builderProxy,
+  builderProxyConfig,
+  builderSocksRemoteDns,
+  builderProxyMode,
+  builderPac,
+  builderProxyPool,
+  builderHosts,
+  builderDnsResolver,
+  builderDoh,
+  builderIpFamily,
+  builderLocalAddress,
+  builderSocketPath,
+  builderRuntime,
+  builderAcceptInvalidCerts,
+  builderAcceptInvalidHostnames,
+  builderRootCertificate,
+  builderTlsBuiltInRootCerts,
+  builderIdentityPkcs12,
+  builderIdentityPem,
+  builderCertificatePins,
   builderLogLevel,
   builderBuild,
 } = require('../rf.node')
 
 const requestPromise = promisify(clientRequest)
+const closePromise = promisify(clientClose)
+const preconnectPromise = promisify(clientPreconnect)
+
+/** Methods sent again after a redirect to a host without a PAC result. */
+const SAFE_METHODS = new Set(['GET', 'HEAD', 'OPTIONS'])
+
+/**
+ * TLS backend the native module was built with, selected by cargo features.
+ */
+export const tlsBackend: 'native-tls' | 'rustls' = rfTlsBackend()
+
+export interface DnsRecord {
+  /**
+     * IPv4 or IPv6 address.
+     */
+  address: string
+
+  /**
+     * Seconds the record may be cached for.
+     */
+  ttl?: number
+}
+
+export interface DohOptions {
+  /**
+     * HTTPS endpoint queried with POST requests, such as `https://cloudflare-dns.com/dns-query`.
+     */
+  url: string
+
+  /**
+     * Addresses of the endpoint's host, so reaching it does not depend on DNS.
+     *
+     * Without bootstrap addresses the host is resolved by the system resolver.
+     */
+  bootstrap?: string[]
+
+  /**
+     * Use the system resolver when the endpoint cannot be queried.
+     *
+     * Negative answers from the endpoint are never retried with the system resolver.
+     *
+     * Defaults to false.
+     */
+  fallback?: boolean
+
+  /**
+     * Timeout in seconds for each query.
+     *
+     * Defaults to 5.
+     */
+  timeout?: number
+}
+
+export interface ProxyPoolOptions {
+  /**
+     * Proxy URLs, http(s), socks5 and socks4.
+     */
+  proxies: string[]
+
+  /**
+     * How the proxy of a request is picked.
+     *
+     * - `round-robin` uses the next proxy for every request.
+     * - `sticky` keeps using the same proxy per origin, also on redirects, until it fails to connect for that origin.
+     * - `random` picks a random proxy for every request.
+     *
+     * Connections are not reused with `round-robin` and `random`, so every request goes through its own proxy,
+     * and `poolMaxIdlePerHost` cannot be set with them. Connections are reused with `sticky`, each origin's
+     * connections going through its proxy.
+     *
+     * Defaults to round-robin.
+     */
+  rotation?: 'round-robin' | 'sticky' | 'random'
+
+  /**
+     * Seconds a proxy that failed to connect is skipped for, while other proxies are available.
+     *
+     * Requests failing to connect are retried through another proxy within `attempts`.
+     *
+     * Defaults to 30.
+     */
+  failoverCooldown?: number
+}
 
 export interface ClientOptions {
   /**
@@ -30,6 +167,42 @@ export interface ClientOptions {
      */
   requestTimeout?: number
 
+  /**
+     * Hard deadline in seconds after which a request rejects with code `ERR_WATCHDOG_TIMEOUT`,
+     * covering retries, certificate pin checks and custom DNS lookups.
+     * Time queued for `maxConcurrentRequests` and `rateLimits` counts toward it.
+     *
+     * Disabled by default.
+     */
+  watchdogTimeout?: number
+
+  /**
+     * Maximum requests in flight, further requests wait in a queue. Unlimited by default.
+     *
+     * Retries of a request do not count separately.
+     */
+  maxConcurrentRequests?: number
+
+  /**
+     * Maximum requests in flight to one origin, further requests to it wait in a queue.
+     * Unlimited by default.
+     */
+  maxConcurrentRequestsPerOrigin?: number
+
+  /**
+     * Order queued requests are started in, `fifo` by default.
+     *
+     * `priority` starts requests with the highest `priority` first, in order among equals.
+     */
+  queueOrder?: 'fifo' | 'priority'
+
+  /**
+     * Token bucket rate limits, the first limit matching an origin applying to it.
+     *
+     * Each attempt of a request takes a token, retries included. Unix socket requests are not limited.
+     */
+  rateLimits?: RateLimit[]
+
   /**
      * Maximum redirects allowed.
      *
@@ -51,15 +224,304 @@ export interface ClientOptions {
 
   /**
      * Use adaptive window size for https2
+     *
+     * Cannot be combined with `http2InitialStreamWindowSize` or `http2InitialConnectionWindowSize`.
      */
   https2AdaptiveWindow?: boolean
 
   /**
-     * Proxy URL.
+     * HTTP/2 SETTINGS_INITIAL_WINDOW_SIZE, from 1 to 2^31 - 1.
+     *
+     * Defaults to 6291456 like Chrome.
+     */
+  http2InitialStreamWindowSize?: number
+
+  /**
+     * HTTP/2 connection flow control window, from 1 to 2^31 - 1.
+     *
+     * Chrome uses 15728640. Defaults to 5242880.
+     */
+  http2InitialConnectionWindowSize?: number
+
+  /**
+     * HTTP/2 SETTINGS_MAX_FRAME_SIZE, from 16384 to 16777215.
+     *
+     * Not sent by default, leaving the protocol default of 16384.
+     */
+  http2MaxFrameSize?: number
+
+  /**
+     * Maximum idle connections kept per host.
+     *
+     * Cannot be combined with a `round-robin` or `random` proxy pool, which does not reuse connections.
+     */
+  poolMaxIdlePerHost?: number
+
+  /**
+     * Seconds an idle pooled connection is kept for, `null` to keep them until closed by the server.
+     *
+     * Defaults to 90.
+     */
+  poolIdleTimeout?: number | null
+
+  /**
+     * Interval in seconds of TCP keepalive probes, disabled by default.
+     */
+  tcpKeepalive?: number
+
+  /**
+     * Whether to disable Nagle's algorithm.
+     *
+     * Defaults to true.
+     */
+  tcpNodelay?: boolean
+
+  /**
+     * Interval in seconds of HTTP/2 PING frames keeping connections alive, disabled by default.
+     */
+  http2KeepAliveInterval?: number
+
+  /**
+     * Seconds to wait for a keep-alive PING acknowledgement before closing the connection.
+     *
+     * Defaults to 20.
+     */
+  http2KeepAliveTimeout?: number
+
+  /**
+     * Whether to send keep-alive PINGs on connections without open streams.
+     *
+     * Defaults to false.
+     */
+  http2KeepAliveWhileIdle?: boolean
+
+  /**
+     * Minimum TLS version.
+     *
+     * TLS 1.3 bounds are not supported by the native-tls backend.
+     */
+  minTlsVersion?: TlsVersion
+
+  /**
+     * Maximum TLS version.
+     *
+     * TLS 1.3 bounds are not supported by the native-tls backend.
+     */
+  maxTlsVersion?: TlsVersion
+
+  /**
+     * ALPN protocols offered over TLS.
+     *
+     * Only `['h2', 'http/1.1']` (default), `['http/1.1']` and `['h2']` are supported,
+     * the latter two being equivalent to `http1Only` and `http2PriorKnowledge`.
+     */
+  alpnProtocols?: ('h2' | 'http/1.1')[]
+
+  /**
+     * Cipher suites offered in the ClientHello, in order, by IANA name, e.g. `TLS_AES_128_GCM_SHA256`.
      *
-     * Http(s) and socks5 proxies are supported.
+     * Only suites implemented by rustls are available, only supported by the rustls backend.
      */
-  proxy?: string
+  tlsCipherSuites?: string[]
+
+  /**
+     * Key exchange groups offered in the ClientHello, in order: `X25519`, `secp256r1` and `secp384r1`.
+     *
+     * Only supported by the rustls backend.
+     */
+  tlsKeyExchangeGroups?: string[]
+
+  /**
+     * Signature algorithms offered in the ClientHello, in order, by IANA name, e.g. `ecdsa_secp256r1_sha256`.
+     *
+     * Only algorithms certificates can be verified with are available, only supported by the rustls backend.
+     */
+  tlsSignatureAlgorithms?: string[]
+
+  /**
+     * Only use HTTP/1.1, offering only `http/1.1` over TLS.
+     */
+  http1Only?: boolean
+
+  /**
+     * Only use HTTP/2, offering only `h2` over TLS and assuming HTTP/2 over plain connections.
+     */
+  http2PriorKnowledge?: boolean
+
+  /**
+     * Proxy URL used for every request, or per-scheme proxy configuration.
+     *
+     * Http(s), socks5 and socks4 proxies are supported. `socks5://` and `socks4://` resolve
+     * hostnames locally, `socks5h://` and `socks4a://` on the proxy so DNS queries do not leave it.
+     * SOCKS4 proxies only take a user ID (`socks4://user@host:port`) and only reach IPv4 addresses.
+     */
+  proxy?: string | ProxyOptions
+
+  /**
+     * Resolve hostnames on the proxy for every `socks5://` and `socks4://` proxy, as with
+     * `socks5h://` and `socks4a://`, including `proxyPool`, `proxyMode` and `pac` proxies.
+     *
+     * Defaults to false.
+     */
+  socksRemoteDns?: boolean
+
+  /**
+     * How the proxy is chosen.
+     *
+     * - `none` always connects directly.
+     * - `env` uses http_proxy, HTTPS_PROXY, ALL_PROXY and NO_PROXY as set when the client is created.
+     *   Like curl, upper case HTTP_PROXY is ignored, as CGI servers set it from the `Proxy` request header.
+     * - `explicit` only uses the `proxy` option.
+     *
+     * Without a mode, the platform's proxy settings apply unless `proxy` is set.
+     */
+  proxyMode?: 'none' | 'env' | 'explicit'
+
+  /**
+     * Proxy auto-config script choosing the proxy per host, given inline or as a file path.
+     *
+     * `FindProxyForURL` is evaluated once per host and the result is kept for the client's lifetime.
+     * The first PROXY, HTTPS, SOCKS5 or SOCKS4 entry is used, DIRECT when none is usable.
+     * `dnsResolve` and the helpers using it look hosts up when the script first asks for them.
+     *
+     * Redirects to hosts the script was not evaluated for are never connected to directly:
+     * GET, HEAD and OPTIONS requests are sent again once it is, other methods fail with
+     * `ERR_PAC_UNRESOLVED` and the redirect target in `url`.
+     *
+     * Cannot be combined with `proxy` or `proxyMode`.
+     */
+  pac?: { source: string } | { path: string }
+
+  /**
+     * Proxies rotated across requests, reported as `proxy` on each response.
+     *
+     * Cannot be combined with `proxy` or `pac`.
+     */
+  proxyPool?: ProxyPoolOptions
+
+  /**
+     * Static host overrides, mapping hostnames to IP addresses.
+     *
+     * Requests keep the hostname for the Host header and TLS SNI and connect to the port of the URL.
+     */
+  hosts?: Record<string, string | string[]>
+
+  /**
+     * Resolves hostnames instead of the system resolver, `hosts` taking precedence.
+     *
+     * Answers are cached for the lowest TTL of their records in seconds, not at all without a TTL.
+     * SOCKS proxies resolving hostnames locally (`socks5://`, `socks4://`) still use the system resolver.
+     */
+  dnsResolver?: (hostname: string) => Promise<DnsRecord[]>
+
+  /**
+     * Resolves hostnames with DNS-over-HTTPS (RFC 8484), cached for the TTL of the answers.
+     *
+     * Cannot be combined with `dnsResolver`.
+     */
+  doh?: DohOptions
+
+  /**
+     * Only connect over IPv4 or IPv6, dropping resolved addresses of the other family.
+     *
+     * URLs with an IP literal and `hosts` overrides are used as given.
+     * Without a family both are tried in resolved order, connecting to the other family too
+     * when the first has not connected within hyper's fixed 300ms happy eyeballs delay.
+     */
+  ipFamily?: 'v4' | 'v6'
+
+  /**
+     * Local IP address outgoing connections are bound to.
+     */
+  localAddress?: string
+
+  /**
+     * Network interface outgoing connections are bound to, through its address.
+     *
+     * The address matching `ipFamily` is used, IPv4 by default. Routing is still up to the
+     * operating system, connections are not bound to the device itself.
+     *
+     * Cannot be combined with `localAddress`.
+     */
+  networkInterface?: string
+
+  /**
+     * Send requests over the Unix socket at this path instead of TCP, such as `/var/run/docker.sock`.
+     *
+     * The request URL only provides the path, query and Host header.
+     * Redirects are followed within the socket's origin up to `redirectLimit`, others are returned as is.
+     * Cookies are reported and gzip and Brotli responses decompressed as for TCP requests.
+     * Proxies, DNS and TLS options do not apply.
+     *
+     * Not supported on Windows.
+     */
+  socketPath?: string
+
+  /**
+     * Runtime requests run on, `shared` by default.
+     *
+     * - `shared`: one multi-threaded runtime for every client in the process, with a thread per core.
+     * - `dedicated`: a multi-threaded runtime for this client only, see `workerThreads`.
+     * - `current-thread`: a single thread for this client only, for a low footprint.
+     */
+  runtime?: 'shared' | 'dedicated' | 'current-thread'
+
+  /**
+     * Worker threads of a `dedicated` runtime, one per core by default.
+     */
+  workerThreads?: number
+
+  /**
+     * Skip TLS certificate verification for this client only.
+     *
+     * Dangerous, any certificate will be trusted. Logged at warn level.
+     */
+  acceptInvalidCerts?: boolean
+
+  /**
+     * Skip TLS hostname verification for this client only.
+     *
+     * Dangerous, a valid certificate for any host will be trusted. Logged at warn level.
+     *
+     * Only supported by the native-tls backend.
+     */
+  acceptInvalidHostnames?: boolean
+
+  /**
+     * Additional root certificates to trust.
+     *
+     * Each entry may be a PEM bundle or a single DER encoded certificate.
+     */
+  rootCertificates?: (string | Buffer)[]
+
+  /**
+     * Whether to trust the platform's built-in root certificates.
+     *
+     * Defaults to true.
+     */
+  tlsBuiltInRootCerts?: boolean
+
+  /**
+     * Client certificate presented for mutual TLS.
+     */
+  identity?: Identity
+
+  /**
+     * SPKI SHA-256 pins per hostname, as `sha256/<base64>`, matching the key of any certificate of the chain.
+     *
+     * Checked after chain validation on every TLS handshake, redirects included,
+     * a mismatch fails requests with the code `ERR_CERT_PIN_MISMATCH`.
+     *
+     * Only supported by the rustls backend, native-tls has no hook to verify certificates.
+     */
+  certificatePins?: Record<string, string[]>
+
+  /**
+     * Log certificate pin mismatches instead of failing requests.
+     *
+     * Defaults to false.
+     */
+  certificatePinsReportOnly?: boolean
 
   /**
      * Logging level.
@@ -70,6 +532,80 @@ export interface ClientOptions {
   logLevel?: LogLevel
 }
 
+export interface ProxyOptions {
+  /**
+     * Proxy URL for http requests, takes precedence over `all`.
+     */
+  http?: string
+
+  /**
+     * Proxy URL for https requests, takes precedence over `all`.
+     */
+  https?: string
+
+  /**
+     * Proxy URL for any request without a scheme specific proxy.
+     */
+  all?: string
+
+  /**
+     * Proxy credentials, sent as basic auth to http(s) proxies and username/password
+     * authentication to SOCKS5 proxies. SOCKS4 proxies only take the username, as user ID.
+     */
+  auth?: {
+    username: string
+
+    password?: string
+  }
+
+  /**
+     * Hosts connected to directly, NO_PROXY style.
+     *
+     * `example.com` matches the domain and its subdomains, `*.example.com` subdomains only,
+     * `10.0.0.0/8` literal IP hosts within the network and `*` everything.
+     */
+  noProxy?: string[]
+
+  /**
+     * Extra headers of the CONNECT request sent to `http://` proxies.
+     *
+     * Plain http requests are tunnelled through CONNECT too, so the headers only reach the proxy.
+     */
+  headers?: Record<string, string>
+
+  /**
+     * Resolve hostnames on the proxy for `socks5://` and `socks4://` proxies, as with `socks5h://` and `socks4a://`.
+     *
+     * Defaults to false.
+     */
+  socksRemoteDns?: boolean
+}
+
+export type TlsVersion = '1.0' | '1.1' | '1.2' | '1.3'
+
+export type Identity =
+  | {
+    /**
+       * PKCS#12 archive containing the certificate chain and private key.
+       *
+       * Only supported by the native-tls backend.
+       */
+    pkcs12: Buffer
+
+    password?: string
+  }
+  | {
+    /**
+       * PEM certificate chain.
+       */
+    cert: string | Buffer
+
+    /**
+       * PEM PKCS#8 private key.
+       */
+    key: string | Buffer
+  }
+
 export enum LogLevel {
   Off = 0,
   Error = 1,
@@ -110,6 +646,82 @@ export interface RequestOptions {
   body?: string | Buffer | FormData
 
   cookieJar?: CookieJar
+
+  /**
+     * Send this request over the Unix socket at this path, overriding the client's `socketPath`.
+     */
+  socketPath?: string
+
+  /**
+     * Priority when queued by a client with `queueOrder: 'priority'`, higher first. Defaults to 0.
+     */
+  priority?: number
+}
+
+export interface RateLimit {
+  /**
+     * Origins the limit applies to, each with a bucket of its own:
+     * `https://api.example.com` for one origin, `api.example.com` for a host with any scheme and port,
+     * `*.example.com` for its subdomains or `*` for every origin.
+     */
+  origin: string
+
+  /**
+     * Requests allowed per `interval`.
+     */
+  requests: number
+
+  /**
+     * Interval in seconds, defaults to 1.
+     */
+  interval?: number
+
+  /**
+     * Requests allowed at once after being idle, defaults to `requests` rounded up.
+     */
+  burst?: number
+}
+
+export interface Timings {
+  /**
+     * Milliseconds waited for a rate limit token and a concurrency slot.
+     */
+  queued: number
+
+  /**
+     * Milliseconds from the request to the body being read, queueing included.
+     */
+  total: number
+}
+
+export interface RequestCounts {
+  inFlight: number
+
+  queued: number
+}
+
+export interface ClientStats extends RequestCounts {
+  /**
+     * Counts of origins with requests in flight or queued.
+     */
+  origins: Record<string, RequestCounts>
+}
+
+export interface ConnectionPool {
+  /**
+     * Protocol of the last response from the origin, null before the first.
+     */
+  httpVersion: string | null
+
+  /**
+     * Requests in flight through the pool.
+     */
+  inFlight: number
+
+  /**
+     * Seconds since the last request through the pool completed, null while requests are in flight.
+     */
+  idleTime: number | null
 }
 
 export interface Response<T> {
@@ -136,12 +748,132 @@ export interface Response<T> {
      * URL => cookies[]
      */
   newCookies: Record<string, string[]>
+
+  timings: Timings
+
+  /**
+     * Proxy the request was sent through without credentials, null when connected directly.
+     *
+     * After redirects, this is the proxy of the last request, the one that got this response.
+     * Only reported when `proxy`, `proxyMode`, `pac` or `proxyPool` is set.
+     */
+  proxy?: string | null
+}
+
+export interface CloseOptions {
+  /**
+     * Abort requests in flight instead of waiting for them.
+     */
+  abort?: boolean
+
+  /**
+     * Seconds to wait for requests in flight before aborting them, waits for them all by default.
+     */
+  timeout?: number
+}
+
+export interface PreconnectResult {
+  /**
+     * Origin the connection was opened to.
+     */
+  origin: string
+
+  /**
+     * Protocol negotiated through ALPN, or HTTP/1.1 over plain connections.
+     */
+  httpVersion: string
+
+  /**
+     * Time taken to resolve, connect and negotiate TLS, in milliseconds.
+     */
+  duration: number
+}
+
+function interfaceAddress(name: string, ipFamily?: 'v4' | 'v6'): string {
+  const family = ipFamily === 'v6' ? 6 : 4
+
+  // Node 18.0 to 18.3 report the family as a number.
+  const entry = networkInterfaces()[name]
+    ?.find(v => v.family === `IPv${family}` || (v.family as unknown) === family)
+
+  if (!entry) {
+    throw new Error(`Network interface ${name} has no IPv${family} address`)
+  }
+
+  return entry.address
 }
 
 export class Client {
   #client: object
 
+  #pac?: PacResolver
+
+  #closing?: Promise<void>
+
   constructor(options: ClientOptions = {}) {
+    // Before building, so an unreadable or invalid script does not leave a native client behind.
+    if (options.pac) {
+      this.#pac = new PacResolver('source' in options.pac ? options.pac.source : readFileSync(options.pac.path, 'utf8'))
+    }
+
+    this.#client = builderBuild.call(this.#configure(options))
+  }
+
+  /**
+   * Connection pools per origin, most recently used first.
+   *
+   * Each origin requested gets a pool of its own, which redirects to other origins go through too.
+   * Pools idle for longer than `poolIdleTimeout` are dropped. hyper does not expose the
+   * connections of a pool, so they are not counted.
+   */
+  public connections(): Record<string, ConnectionPool> {
+    return clientConnections.call(this.#client)
+  }
+
+  /**
+   * Closes the connection pool of the origin of `url`, returning whether it had one.
+   *
+   * Idle connections close right away, unless requests are in flight through the pool, in which
+   * case they close along with the connections in use once those requests complete.
+   */
+  public closeIdleConnections(url: string): boolean {
+    return clientCloseIdleConnections.call(this.#client, url)
+  }
+
+  /**
+   * Closes pooled connections, for example after a network change left them stale.
+   *
+   * Idle connections close right away and connections in use once their requests complete,
+   * without rebuilding the runtime or reading the options again. Cached DNS answers are dropped as well.
+   */
+  public resetConnections(): void {
+    clientResetConnections.call(this.#client)
+  }
+
+  /**
+   * Stops accepting requests, then waits for requests in flight and stops the runtime off the
+   * main thread, unless it is the shared runtime.
+   *
+   * Aborted requests reject with code `ERR_CLIENT_CLOSED`, as do requests made after closing.
+   * Closing again returns the same promise.
+   */
+  public close(options: CloseOptions = {}): Promise<void> {
+    this.#closing ??= closePromise.call(this.#client, options.abort ?? false, options.timeout ?? null)
+
+    return this.#closing
+  }
+
+  /**
+   * Requests in flight and queued, in total and per origin.
+   */
+  public stats(): ClientStats {
+    return clientStats.call(this.#client)
+  }
+
+  /**
+   * Native builder configured with the options.
+   */
+  #configure(options: ClientOptions): object {
     let builder = builderNew()
 
     if (options.connectTimeout) {
@@ -152,6 +884,23 @@ export class Client {
       builder = builderRequestTimeout.call(builder, options.requestTimeout)
     }
 
+    if (options.watchdogTimeout) {
+      builder = builderWatchdogTimeout.call(builder, options.watchdogTimeout)
+    }
+
+    for (const limit of options.rateLimits ?? []) {
+      builder = builderRateLimit.call(builder, limit.origin, limit.requests, limit.interval ?? 1, limit.burst ?? Math.ceil(limit.requests))
+    }
+
+    if (options.maxConcurrentRequests !== undefined || options.maxConcurrentRequestsPerOrigin !== undefined || options.queueOrder) {
+      builder = builderConcurrencyLimits.call(
+        builder,
+        options.maxConcurrentRequests ?? null,
+        options.maxConcurrentRequestsPerOrigin ?? null,
+        options.queueOrder ?? 'fifo',
+      )
+    }
+
     // JS is type juggling 0 to false
     if (options.redirectLimit !== undefined) {
       builder = builderRedirectLimit.call(builder, options.redirectLimit)
@@ -169,15 +918,221 @@ export class Client {
       builder = builderHttps2AdaptiveWindow.call(builder, options.https2AdaptiveWindow)
     }
 
-    if (options.proxy) {
+    if (options.http2InitialStreamWindowSize !== undefined) {
+      builder = builderHttp2InitialStreamWindowSize.call(builder, options.http2InitialStreamWindowSize)
+    }
+
+    if (options.http2InitialConnectionWindowSize !== undefined) {
+      builder = builderHttp2InitialConnectionWindowSize.call(builder, options.http2InitialConnectionWindowSize)
+    }
+
+    if (options.http2MaxFrameSize !== undefined) {
+      builder = builderHttp2MaxFrameSize.call(builder, options.http2MaxFrameSize)
+    }
+
+    if (options.poolMaxIdlePerHost !== undefined) {
+      builder = builderPoolMaxIdlePerHost.call(builder, options.poolMaxIdlePerHost)
+    }
+
+    if (options.poolIdleTimeout !== undefined) {
+      builder = builderPoolIdleTimeout.call(builder, options.poolIdleTimeout)
+    }
+
+    if (options.tcpKeepalive !== undefined) {
+      builder = builderTcpKeepalive.call(builder, options.tcpKeepalive)
+    }
+
+    if (options.tcpNodelay !== undefined) {
+      builder = builderTcpNodelay.call(builder, options.tcpNodelay)
+    }
+
+    if (options.http2KeepAliveInterval !== undefined) {
+      builder = builderHttp2KeepAliveInterval.call(builder, options.http2KeepAliveInterval)
+    }
+
+    if (options.http2KeepAliveTimeout !== undefined) {
+      builder = builderHttp2KeepAliveTimeout.call(builder, options.http2KeepAliveTimeout)
+    }
+
+    if (options.http2KeepAliveWhileIdle !== undefined) {
+      builder = builderHttp2KeepAliveWhileIdle.call(builder, options.http2KeepAliveWhileIdle)
+    }
+
+    if (options.minTlsVersion) {
+      builder = builderMinTlsVersion.call(builder, options.minTlsVersion)
+    }
+
+    if (options.maxTlsVersion) {
+      builder = builderMaxTlsVersion.call(builder, options.maxTlsVersion)
+    }
+
+    if (options.alpnProtocols) {
+      builder = builderAlpnProtocols.call(builder, options.alpnProtocols)
+    }
+
+    if (options.tlsCipherSuites) {
+      builder = builderTlsCipherSuites.call(builder, options.tlsCipherSuites)
+    }
+
+    if (options.tlsKeyExchangeGroups) {
+      builder = builderTlsKeyExchangeGroups.call(builder, options.tlsKeyExchangeGroups)
+    }
+
+    if (options.tlsSignatureAlgorithms) {
+      builder = builderTlsSignatureAlgorithms.call(builder, options.tlsSignatureAlgorithms)
+    }
+
+    if (options.http1Only) {
+      builder = builderHttp1Only.call(builder)
+    }
+
+    if (options.http2PriorKnowledge) {
+      builder = builderHttp2PriorKnowledge.call(builder)
+    }
+
+    if (typeof options.proxy === 'string') {
       builder = builderProxy.call(builder, options.proxy)
+    } else if (options.proxy) {
+      const { auth, ...proxy } = options.proxy
+
+      builder = builderProxyConfig.call(builder, { ...proxy, ...auth })
+    }
+
+    if (options.hosts) {
+      const hosts = Object.fromEntries(
+        Object.entries(options.hosts).map(([host, addresses]) => [host, ([] as string[]).concat(addresses)]),
+      )
+
+      builder = builderHosts.call(builder, hosts)
+    }
+
+    if (options.dnsResolver) {
+      const { dnsResolver } = options
+
+      builder = builderDnsResolver.call(builder, (hostname: string, pending: object) => {
+        // Invalid records throw in the completion, failing the lookup with that error instead.
+        Promise.resolve()
+          .then(() => dnsResolver(hostname))
+          .then(records => dnsLookupComplete.call(pending, null, records))
+          .catch(err => dnsLookupComplete.call(pending, String(err?.message ?? err)))
+      })
+    }
+
+    if (options.doh) {
+      const { url, bootstrap, fallback, timeout } = options.doh
+
+      builder = builderDoh.call(builder, url, bootstrap ?? [], fallback ?? false, timeout ?? 5)
+    }
+
+    if (options.ipFamily) {
+      builder = builderIpFamily.call(builder, options.ipFamily)
+    }
+
+    if (options.localAddress && options.networkInterface) {
+      throw new Error('localAddress cannot be combined with networkInterface')
+    }
+
+    if (options.localAddress) {
+      builder = builderLocalAddress.call(builder, options.localAddress)
+    } else if (options.networkInterface) {
+      builder = builderLocalAddress.call(builder, interfaceAddress(options.networkInterface, options.ipFamily))
+    }
+
+    if (options.socketPath) {
+      builder = builderSocketPath.call(builder, options.socketPath)
+    }
+
+    if (options.runtime || options.workerThreads !== undefined) {
+      builder = builderRuntime.call(builder, options.runtime ?? 'shared', options.workerThreads ?? null)
+    }
+
+    if (options.acceptInvalidCerts) {
+      builder = builderAcceptInvalidCerts.call(builder, options.acceptInvalidCerts)
+    }
+
+    if (options.acceptInvalidHostnames) {
+      builder = builderAcceptInvalidHostnames.call(builder, options.acceptInvalidHostnames)
+    }
+
+    for (const cert of options.rootCertificates ?? []) {
+      builder = builderRootCertificate.call(builder, cert)
+    }
+
+    if (options.tlsBuiltInRootCerts !== undefined) {
+      builder = builderTlsBuiltInRootCerts.call(builder, options.tlsBuiltInRootCerts)
+    }
+
+    if (options.identity) {
+      builder = 'pkcs12' in options.identity
+        ? builderIdentityPkcs12.call(builder, options.identity.pkcs12, options.identity.password ?? '')
+        : builderIdentityPem.call(builder, options.identity.cert, options.identity.key)
+    }
+
+    if (options.proxyMode) {
+      builder = builderProxyMode.call(builder, options.proxyMode, process.env)
+    }
+
+    if (options.proxyPool) {
+      if (options.proxy || options.pac) {
+        throw new Error('A proxy pool cannot be combined with proxy or pac')
+      }
+
+      const { proxies, rotation, failoverCooldown } = options.proxyPool
+
+      builder = builderProxyPool.call(builder, proxies, rotation ?? 'round-robin', failoverCooldown ?? 30)
+    }
+
+    if (options.pac) {
+      if (options.proxy || options.proxyMode) {
+        throw new Error('A PAC script cannot be combined with proxy or proxyMode')
+      }
+
+      builder = builderPac.call(builder)
+    }
+
+    if (options.socksRemoteDns) {
+      builder = builderSocksRemoteDns.call(builder, true)
+    }
+
+    if (options.certificatePins) {
+      builder = builderCertificatePins.call(builder, options.certificatePins, options.certificatePinsReportOnly ?? false)
     }
 
     builder = builderLogLevel.call(builder, options.logLevel ?? LogLevel.Info)
 
-    this.#client = builderBuild.call(builder)
+    return builder
+  }
+
+  /**
+   * Resolves, connects and negotiates TLS with an origin ahead of requests to it, then leaves
+   * the connection in the pool.
+   *
+   * Connecting takes a `HEAD /` request to the origin, whose redirects are not followed and whose
+   * cookies are not stored, and `maxConcurrentRequests` and `rateLimits` do not apply. Rejects
+   * like `request` when the origin cannot be reached, without retrying.
+   */
+  public async preconnect(origin: string): Promise<PreconnectResult> {
+    const url = new URL(origin)
+
+    if (url.protocol !== 'http:' && url.protocol !== 'https:') {
+      throw new Error(`Invalid origin: ${origin}`)
+    }
+
+    if (this.#pac) {
+      const [host, proxy, isNew] = await this.#pac.resolve(url.origin)
+
+      if (isNew) clientSetPacProxy.call(this.#client, host, proxy)
+    }
+
+    const { httpVersion, duration } = await preconnectPromise.call(this.#client, url.origin)
+
+    return { origin: url.origin, httpVersion, duration }
   }
 
+  /**
+   * Sends a request, which always settles: unexpected failures in the native module reject
+   * with code `ERR_INTERNAL`.
+   */
   public async request<T>(url: string, args: RequestOptions = {}): Promise<Response<T>> {
     const options = {
       method: 'GET',
@@ -198,7 +1153,7 @@ export class Client {
       options.body = (args.body as FormData).getBuffer()
     }
 
-    const res: Response<T> = await requestPromise.call(this.#client, url, options)
+    const res: Response<T> = await this.#send(url, options)
 
     if (args.cookieJar) {
       for (const [k, v] of Object.entries(res.newCookies)) {
@@ -210,4 +1165,36 @@ export class Client {
 
     return res
   }
+
+  /**
+   * Sends a request, first evaluating the PAC script for its host.
+   *
+   * Redirects to hosts the script was not evaluated for fail with `ERR_PAC_UNRESOLVED`
+   * instead of connecting directly. Safe requests are then sent again once the script
+   * is evaluated for the redirect target, other methods are not repeated.
+   */
+  async #send<T>(url: string, options: RequestOptions): Promise<Response<T>> {
+    if (!this.#pac) return requestPromise.call(this.#client, url, options)
+
+    let target = url
+
+    for (;;) {
+      const [host, proxy, isNew] = await this.#pac.resolve(target)
+
+      if (isNew) clientSetPacProxy.call(this.#client, host, proxy)
+
+      try {
+        return await requestPromise.call(this.#client, url, options)
+      } catch (err) {
+        const { code, url: redirect } = err as { code?: string, url?: string }
+
+        if (code !== 'ERR_PAC_UNRESOLVED' || !SAFE_METHODS.has(options.method ?? 'GET')) throw err
+
+        // Each round evaluates a new host, a known one failing again would loop.
+        if (!redirect || this.#pac.has(redirect)) throw err
+
+        target = redirect
+      }
+    }
+  }
 }
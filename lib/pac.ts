@@ -0,0 +1,199 @@
+import * as os from 'os'
+import * as vm from 'vm'
+import { promises as dns } from 'dns'
+import { isIP } from 'net'
+
+const WEEKDAYS = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT']
+
+/**
+ * Milliseconds a script run or `FindProxyForURL` call may take.
+ */
+const PAC_TIMEOUT = 1000
+
+/**
+ * Calls `FindProxyForURL` within the context, so the call is bound by `PAC_TIMEOUT` too.
+ */
+const FIND_PROXY_CALL = new vm.Script('FindProxyForURL(__pacUrl, __pacHost)', { filename: 'proxy.pac' })
+
+function ipToNumber(ip: string): number {
+  return ip.split('.').reduce((acc, octet) => (acc * 256) + Number(octet), 0)
+}
+
+function myIpAddress(): string {
+  for (const entries of Object.values(os.networkInterfaces())) {
+    for (const entry of entries ?? []) {
+      if (entry.family === 'IPv4' && !entry.internal) return entry.address
+    }
+  }
+
+  return '127.0.0.1'
+}
+
+/**
+ * Thrown by `dnsResolve` for hosts not looked up yet, to evaluate again once they are.
+ */
+class PendingLookup {
+  readonly host: string
+
+  constructor(host: string) {
+    this.host = host
+  }
+}
+
+/**
+ * Standard PAC helper functions.
+ *
+ * PAC scripts are synchronous while DNS in node is not, so `dnsResolve` only knows
+ * hosts looked up before. Other hosts abort the evaluation, which is run again after
+ * looking them up, so DNS is only queried for hosts the script asks about.
+ * `dateRange` is not supported and always returns false.
+ */
+function pacHelpers(resolved: Map<string, string | null>) {
+  const dnsResolve = (host: string) => {
+    if (isIP(host)) return host
+
+    const address = resolved.get(host)
+
+    if (address === undefined) throw new PendingLookup(host)
+
+    return address
+  }
+
+  return {
+    isPlainHostName: (host: string) => !host.includes('.'),
+    dnsDomainIs: (host: string, domain: string) => host.endsWith(domain),
+    localHostOrDomainIs: (host: string, hostdom: string) => host === hostdom || (!host.includes('.') && hostdom.startsWith(`${host}.`)),
+    isResolvable: (host: string) => dnsResolve(host) !== null,
+    isInNet: (host: string, pattern: string, mask: string) => {
+      const ip = dnsResolve(host)
+
+      if (!ip || isIP(ip) !== 4) return false
+
+      const m = ipToNumber(mask)
+
+      // eslint-disable-next-line no-bitwise
+      return ((ipToNumber(ip) & m) >>> 0) === ((ipToNumber(pattern) & m) >>> 0)
+    },
+    dnsResolve,
+    convert_addr: ipToNumber,
+    myIpAddress,
+    dnsDomainLevels: (host: string) => host.split('.').length - 1,
+    shExpMatch: (str: string, exp: string) => {
+      const re = exp.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.')
+
+      return new RegExp(`^${re}$`).test(str)
+    },
+    weekdayRange: (wd1: string, wd2?: string, gmt?: string) => {
+      const now = new Date()
+      const day = gmt === 'GMT' || wd2 === 'GMT' ? now.getUTCDay() : now.getDay()
+      const start = WEEKDAYS.indexOf(wd1)
+      const end = wd2 && wd2 !== 'GMT' ? WEEKDAYS.indexOf(wd2) : start
+
+      return start <= end ? day >= start && day <= end : day >= start || day <= end
+    },
+    dateRange: () => false,
+    timeRange: (...args: (number | string)[]) => {
+      const gmt = args[args.length - 1] === 'GMT'
+      const [h1, h2] = args.filter(a => a !== 'GMT') as number[]
+      const now = new Date()
+      const hour = gmt ? now.getUTCHours() : now.getHours()
+
+      return h2 === undefined ? hour === h1 : hour >= h1 && hour < h2
+    },
+  }
+}
+
+/**
+ * Converts the first usable `FindProxyForURL` entry to a proxy URL, empty for DIRECT.
+ *
+ * `SOCKS` is taken as SOCKS5, which most proxies announced that way speak.
+ */
+export function parsePacResult(result: string): string {
+  for (const entry of result.split(';')) {
+    const [type, address] = entry.trim().split(/\s+/)
+
+    switch (type?.toUpperCase()) {
+      case 'DIRECT':
+        return ''
+      case 'PROXY':
+      case 'HTTP':
+        return `http://${address}`
+      case 'HTTPS':
+        return `https://${address}`
+      case 'SOCKS':
+      case 'SOCKS5':
+        return `socks5://${address}`
+      case 'SOCKS4':
+        return `socks4://${address}`
+      default:
+        break
+    }
+  }
+
+  return ''
+}
+
+/**
+ * Evaluates a PAC script, caching the chosen proxy per host.
+ */
+export class PacResolver {
+  #context: vm.Context
+
+  #resolved = new Map<string, string | null>()
+
+  #cache = new Map<string, string>()
+
+  constructor(source: string) {
+    const context = vm.createContext(pacHelpers(this.#resolved))
+
+    vm.runInContext(source, context, { filename: 'proxy.pac', timeout: PAC_TIMEOUT })
+
+    if (typeof context.FindProxyForURL !== 'function') {
+      throw new Error('PAC script does not define FindProxyForURL')
+    }
+
+    this.#context = context
+  }
+
+  /**
+   * Returns the cache key and proxy URL for `url`, an empty proxy meaning DIRECT.
+   *
+   * The boolean is true when the host was evaluated for the first time.
+   */
+  async resolve(url: string): Promise<[string, string, boolean]> {
+    const { host, hostname } = new URL(url)
+
+    const cached = this.#cache.get(host)
+
+    if (cached !== undefined) return [host, cached, false]
+
+    const proxy = parsePacResult(await this.#evaluate(url, hostname))
+
+    this.#cache.set(host, proxy)
+
+    return [host, proxy, true]
+  }
+
+  /**
+   * Whether the script was evaluated for the host of `url`.
+   */
+  has(url: string): boolean {
+    return this.#cache.has(new URL(url).host)
+  }
+
+  async #evaluate(url: string, hostname: string): Promise<string> {
+    for (;;) {
+      try {
+        Object.assign(this.#context, { __pacUrl: url, __pacHost: hostname })
+
+        return String(FIND_PROXY_CALL.runInContext(this.#context, { timeout: PAC_TIMEOUT }))
+      } catch (err) {
+        if (!(err instanceof PendingLookup)) throw err
+
+        const address = await dns.lookup(err.host, { family: 4 }).then(r => r.address, () => null)
+
+        this.#resolved.set(err.host, address)
+      }
+    }
+  }
+}
@@ -0,0 +1,512 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use log::{debug, warn};
+
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Time for a connection through the bridge to be granted by the upstream proxy.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Handed to reqwest when the bridge cannot start, so requests fail rather than go direct.
+const UNREACHABLE: &str = "socks5h://127.0.0.1:0";
+
+const SOCKS4_VERSION: u8 = 0x04;
+const SOCKS5_VERSION: u8 = 0x05;
+const CMD_CONNECT: u8 = 0x01;
+const METHOD_PASSWORD: u8 = 0x02;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+const SOCKS4_GRANTED: u8 = 0x5a;
+
+/// Largest CONNECT response head read from an HTTP proxy.
+const MAX_RESPONSE_HEAD: usize = 8192;
+
+/// SOCKS5 reply codes (RFC 1928 6).
+const REPLY_SUCCEEDED: u8 = 0x00;
+const REPLY_FAILURE: u8 = 0x01;
+const REPLY_HOST_UNREACHABLE: u8 = 0x04;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+/// Local SOCKS5 server relaying connections to proxies reqwest cannot connect through itself.
+///
+/// reqwest's SOCKS connector only speaks SOCKS5 and its CONNECT requests only carry
+/// `Proxy-Authorization`, so SOCKS4 proxies, and HTTP proxies when extra headers are
+/// configured, are handed to it as this bridge. The username picks the upstream proxy and a
+/// per-bridge secret is the password so other local processes cannot relay through it.
+/// Started on first use, within the runtime of the request.
+#[derive(Debug, Default)]
+pub struct ProxyBridge {
+    listening: Mutex<Option<Listening>>,
+
+    /// Extra headers of CONNECT requests to HTTP proxies.
+    headers: HeaderMap,
+}
+
+#[derive(Debug)]
+struct Listening {
+    addr: SocketAddr,
+
+    relay: Arc<Relay>,
+
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct Relay {
+    secret: String,
+
+    headers: HeaderMap,
+
+    upstreams: RwLock<Vec<Url>>,
+}
+
+enum Target {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+impl ProxyBridge {
+    /// Sends `headers` with every CONNECT request to HTTP proxies, plain HTTP requests
+    /// included, which are tunnelled so the headers never reach the origin.
+    pub fn with_headers(headers: HeaderMap) -> Self {
+        Self {
+            listening: Mutex::default(),
+            headers,
+        }
+    }
+
+    pub fn has_headers(&self) -> bool {
+        !self.headers.is_empty()
+    }
+
+    /// Proxy URL for reqwest to connect through to reach `proxy`, unchanged unless it has to
+    /// go through the bridge.
+    pub fn route(&self, proxy: Url) -> Url {
+        let bridged = match proxy.scheme() {
+            "socks4" | "socks4a" => true,
+            "http" => self.has_headers(),
+            _ => false,
+        };
+
+        if !bridged {
+            return proxy;
+        }
+
+        let mut listening = self.listening.lock().unwrap();
+
+        if listening.is_none() {
+            match Listening::start(self.headers.clone()) {
+                Ok(v) => *listening = Some(v),
+                Err(e) => {
+                    warn!("Could not start the proxy bridge: {}", e);
+
+                    return Url::parse(UNREACHABLE).unwrap();
+                }
+            }
+        }
+
+        let listening = listening.as_ref().unwrap();
+
+        let index = listening.relay.register(proxy);
+
+        let mut url = Url::parse(&format!("socks5h://{}", listening.addr)).unwrap();
+        url.set_username(&index.to_string()).unwrap();
+        url.set_password(Some(&listening.relay.secret)).unwrap();
+
+        url
+    }
+}
+
+impl Drop for ProxyBridge {
+    fn drop(&mut self) {
+        if let Some(listening) = self.listening.get_mut().unwrap() {
+            listening.task.abort();
+        }
+    }
+}
+
+impl Listening {
+    fn start(headers: HeaderMap) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+
+        let addr = listener.local_addr()?;
+
+        tokio::runtime::Handle::try_current()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let listener = TcpListener::from_std(listener)?;
+
+        let mut secret = [0u8; 16];
+        getrandom::getrandom(&mut secret)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+        let relay = Arc::new(Relay {
+            secret: secret.iter().map(|b| format!("{:02x}", b)).collect(),
+            headers,
+            upstreams: RwLock::new(Vec::new()),
+        });
+
+        debug!("Proxy bridge listening on {}", addr);
+
+        let task = tokio::spawn(Relay::accept(relay.clone(), listener));
+
+        Ok(Self { addr, relay, task })
+    }
+}
+
+impl Relay {
+    fn register(&self, proxy: Url) -> usize {
+        if let Some(i) = self
+            .upstreams
+            .read()
+            .unwrap()
+            .iter()
+            .position(|v| *v == proxy)
+        {
+            return i;
+        }
+
+        let mut upstreams = self.upstreams.write().unwrap();
+
+        match upstreams.iter().position(|v| *v == proxy) {
+            Some(i) => i,
+            None => {
+                upstreams.push(proxy);
+                upstreams.len() - 1
+            }
+        }
+    }
+
+    async fn accept(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Proxy bridge could not accept a connection: {}", e);
+
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            let relay = self.clone();
+
+            tokio::spawn(async move {
+                if let Err(e) = relay.relay(stream).await {
+                    debug!("Proxy bridge connection failed: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn relay(&self, mut client: TcpStream) -> io::Result<()> {
+        let mut upstream = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.handshake(&mut client))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Proxy handshake timed out"))??;
+
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+
+        Ok(())
+    }
+
+    /// Accepts a SOCKS5 CONNECT from reqwest and connects through the upstream proxy it names.
+    async fn handshake(&self, client: &mut TcpStream) -> io::Result<TcpStream> {
+        let mut greeting = [0u8; 2];
+        client.read_exact(&mut greeting).await?;
+
+        let mut methods = vec![0u8; greeting[1] as usize];
+        client.read_exact(&mut methods).await?;
+
+        if greeting[0] != SOCKS5_VERSION || !methods.contains(&METHOD_PASSWORD) {
+            client
+                .write_all(&[SOCKS5_VERSION, NO_ACCEPTABLE_METHODS])
+                .await?;
+
+            return Err(invalid(
+                "client did not offer username/password authentication",
+            ));
+        }
+
+        client.write_all(&[SOCKS5_VERSION, METHOD_PASSWORD]).await?;
+
+        // Username/password authentication (RFC 1929).
+        let mut version = [0u8; 1];
+        client.read_exact(&mut version).await?;
+
+        let username = read_string(client).await?;
+        let password = read_string(client).await?;
+
+        let upstream = username
+            .parse::<usize>()
+            .ok()
+            .filter(|_| password == self.secret)
+            .and_then(|i| self.upstreams.read().unwrap().get(i).cloned());
+
+        let upstream = match upstream {
+            Some(v) => v,
+            None => {
+                client.write_all(&[0x01, 0x01]).await?;
+
+                return Err(invalid("authentication failed"));
+            }
+        };
+
+        client.write_all(&[0x01, 0x00]).await?;
+
+        let mut request = [0u8; 4];
+        client.read_exact(&mut request).await?;
+
+        let target = match request[3] {
+            ATYP_IPV4 => {
+                let mut ip = [0u8; 4];
+                client.read_exact(&mut ip).await?;
+
+                Target::Ip(Ipv4Addr::from(ip).into())
+            }
+            ATYP_DOMAIN => Target::Domain(read_string(client).await?),
+            ATYP_IPV6 => {
+                let mut ip = [0u8; 16];
+                client.read_exact(&mut ip).await?;
+
+                Target::Ip(Ipv6Addr::from(ip).into())
+            }
+            _ => return Err(invalid("unknown address type")),
+        };
+
+        let mut port = [0u8; 2];
+        client.read_exact(&mut port).await?;
+
+        let port = u16::from_be_bytes(port);
+
+        if request[1] != CMD_CONNECT {
+            reply(client, REPLY_COMMAND_NOT_SUPPORTED).await?;
+
+            return Err(invalid("only CONNECT is supported"));
+        }
+
+        let connected = match upstream.scheme() {
+            "http" => connect_http(&upstream, &self.headers, target, port).await,
+            // SOCKS4 only addresses IPv4 destinations.
+            _ if matches!(target, Target::Ip(IpAddr::V6(_))) => {
+                reply(client, REPLY_ADDRESS_NOT_SUPPORTED).await?;
+
+                return Err(invalid("IPv6 destinations are not supported by SOCKS4"));
+            }
+            _ => connect_socks4(&upstream, target, port).await,
+        };
+
+        match connected {
+            Ok(stream) => {
+                reply(client, REPLY_SUCCEEDED).await?;
+
+                Ok(stream)
+            }
+            Err(e) => {
+                let code = match e.kind() {
+                    io::ErrorKind::NotFound => REPLY_HOST_UNREACHABLE,
+                    _ => REPLY_FAILURE,
+                };
+
+                reply(client, code).await?;
+
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Sends a SOCKS4 CONNECT (SOCKS4a for hostnames with `socks4a://` proxies).
+async fn connect_socks4(upstream: &Url, target: Target, port: u16) -> io::Result<TcpStream> {
+    let socks4a = upstream.scheme() == "socks4a";
+
+    let (ip, hostname) = match target {
+        Target::Ip(IpAddr::V4(ip)) => (ip, None),
+        Target::Ip(IpAddr::V6(_)) => unreachable!("SOCKS4 targets are IPv4"),
+        // SOCKS4a marks hostnames with the invalid address 0.0.0.x.
+        Target::Domain(name) if socks4a => (Ipv4Addr::new(0, 0, 0, 1), Some(name)),
+        Target::Domain(name) => {
+            let ip = lookup_host((name.as_str(), port))
+                .await?
+                .find_map(|addr| match addr.ip() {
+                    IpAddr::V4(ip) => Some(ip),
+                    IpAddr::V6(_) => None,
+                })
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("No IPv4 address for {}", name),
+                    )
+                })?;
+
+            (ip, None)
+        }
+    };
+
+    let mut stream = connect_upstream(upstream, 1080).await?;
+
+    let mut request = vec![SOCKS4_VERSION, CMD_CONNECT];
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&ip.octets());
+    request.extend(percent_decode(upstream.username()));
+    request.push(0);
+
+    if let Some(hostname) = hostname {
+        request.extend_from_slice(hostname.as_bytes());
+        request.push(0);
+    }
+
+    stream.write_all(&request).await?;
+
+    let mut response = [0u8; 8];
+    stream.read_exact(&mut response).await?;
+
+    if response[1] != SOCKS4_GRANTED {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!(
+                "SOCKS4 proxy rejected the request with {:#04x}",
+                response[1]
+            ),
+        ));
+    }
+
+    Ok(stream)
+}
+
+/// Sends an HTTP CONNECT with the configured headers and credentials of the proxy URL.
+async fn connect_http(
+    upstream: &Url,
+    headers: &HeaderMap,
+    target: Target,
+    port: u16,
+) -> io::Result<TcpStream> {
+    let authority = match target {
+        Target::Ip(IpAddr::V6(ip)) => format!("[{}]:{}", ip, port),
+        Target::Ip(ip) => format!("{}:{}", ip, port),
+        Target::Domain(name) => format!("{}:{}", name, port),
+    };
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority).into_bytes();
+
+    if !upstream.username().is_empty() {
+        let mut credentials = percent_decode(upstream.username());
+        credentials.push(b':');
+        credentials.extend(percent_decode(upstream.password().unwrap_or_default()));
+
+        request.extend_from_slice(b"Proxy-Authorization: Basic ");
+        request.extend_from_slice(BASE64.encode(credentials).as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+
+    for (name, value) in headers {
+        request.extend_from_slice(name.as_str().as_bytes());
+        request.extend_from_slice(b": ");
+        request.extend_from_slice(value.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+
+    request.extend_from_slice(b"\r\n");
+
+    let mut stream = connect_upstream(upstream, 80).await?;
+
+    stream.write_all(&request).await?;
+
+    // Read byte by byte so nothing past the response head is taken from the tunnel.
+    let mut head = Vec::new();
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(invalid("CONNECT response head is too large"));
+        }
+
+        head.push(stream.read_u8().await?);
+    }
+
+    let status = head
+        .split(|&b| b == b' ')
+        .nth(1)
+        .and_then(|v| std::str::from_utf8(v).ok())
+        .and_then(|v| v.parse::<u16>().ok());
+
+    match status {
+        Some(200..=299) => Ok(stream),
+        Some(status) => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("HTTP proxy rejected the CONNECT request with {}", status),
+        )),
+        None => Err(invalid("invalid CONNECT response")),
+    }
+}
+
+async fn connect_upstream(upstream: &Url, default_port: u16) -> io::Result<TcpStream> {
+    let host = upstream
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+
+    TcpStream::connect((host, upstream.port().unwrap_or(default_port))).await
+}
+
+async fn reply(client: &mut TcpStream, code: u8) -> io::Result<()> {
+    client
+        .write_all(&[SOCKS5_VERSION, code, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
+/// Reads a length prefixed SOCKS5 field.
+async fn read_string(client: &mut TcpStream) -> io::Result<String> {
+    let mut length = [0u8; 1];
+    client.read_exact(&mut length).await?;
+
+    let mut value = vec![0u8; length[0] as usize];
+    client.read_exact(&mut value).await?;
+
+    String::from_utf8(value).map_err(|_| invalid("field is not UTF-8"))
+}
+
+/// User ID or password of a proxy URL, which `Url` keeps percent-encoded.
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|v| std::str::from_utf8(v).ok())
+            .and_then(|v| u8::from_str_radix(v, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -1,32 +1,192 @@
 use std::cell::RefCell;
+use std::convert::TryInto;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use env_logger::Builder as LoggerBuilder;
-use log::LevelFilter;
+use log::{warn, LevelFilter};
 
 use neon::prelude::*;
+use neon::types::buffer::TypedArray;
 
-use tokio::runtime::Runtime;
-
+use reqwest::header::HeaderMap;
 use reqwest::redirect::Policy;
-use reqwest::{ClientBuilder, Proxy};
-
-use crate::client::Client;
+use reqwest::tls::Version;
+use reqwest::{Certificate, Client as ReqwestClient, ClientBuilder, Identity, Proxy, Url};
+
+#[cfg(unix)]
+use hyper::Client as HyperClient;
+
+use crate::bridge::ProxyBridge;
+use crate::client::{self, Client};
+use crate::dns::{
+    CachingResolver, DohConfig, DohResolver, FamilyFilter, HostResolver, IpFamily, JsResolver,
+    SystemResolver,
+};
+use crate::lifecycle::Lifecycle;
+use crate::limiter::{Limiter, QueueOrder};
+use crate::pool::OriginPools;
+#[cfg(feature = "rustls")]
+use crate::pinning::Pins;
+use crate::proxy::{Bypass, PacUnresolved, ProxyMode, ProxyPool, ProxyRules, Rotation};
+use crate::rate_limit::{OriginPattern, RateLimit, RateLimiter};
+use crate::runtime::{ClientRuntime, RuntimeMode};
 use crate::time_jar::TimeJar;
+#[cfg(feature = "rustls")]
+use crate::tls::{self, TlsOptions};
+#[cfg(unix)]
+use crate::unix::UnixConnector;
 
 pub struct Builder(Option<BuilderInner>);
 
+/// HTTP version restriction, which also decides the ALPN protocols offered over TLS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpVersionPref {
+    /// ALPN `http/1.1` only.
+    Http1,
+
+    /// ALPN `h2` only, and HTTP/2 with prior knowledge over plain connections.
+    Http2,
+}
+
+/// Redirects followed by reqwest's default policy.
+const DEFAULT_REDIRECT_LIMIT: usize = 10;
+
+/// reqwest's default pool idle timeout.
+const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Largest HTTP/2 flow control window (RFC 7540 6.9.1).
+const HTTP2_MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+
+/// Allowed HTTP/2 SETTINGS_MAX_FRAME_SIZE values (RFC 7540 6.5.2).
+const HTTP2_FRAME_SIZES: RangeInclusive<u32> = 16_384..=16_777_215;
+
+/// Stream window sent by default, matching Chrome's SETTINGS_INITIAL_WINDOW_SIZE.
+const HTTP2_DEFAULT_STREAM_WINDOW: u32 = 1024 * 256 * 24;
+
+/// HTTP/2 flow control and framing as given, validated when the client is built.
+#[derive(Debug, Default)]
+struct Http2Settings {
+    adaptive_window: bool,
+
+    stream_window: Option<f64>,
+
+    connection_window: Option<f64>,
+
+    max_frame_size: Option<f64>,
+}
+
+/// reqwest client configuration, recorded to build the client again with an empty pool.
+///
+/// Steps share what they capture, such as proxy rules and DNS resolvers, across builds.
+#[derive(Clone, Default)]
+pub struct ClientSteps(Vec<Arc<dyn Fn(ClientBuilder) -> ClientBuilder + Send + Sync>>);
+
+impl ClientSteps {
+    fn then(mut self, step: impl Fn(ClientBuilder) -> ClientBuilder + Send + Sync + 'static) -> Self {
+        self.0.push(Arc::new(step));
+        self
+    }
+
+    pub fn build(&self) -> reqwest::Result<ReqwestClient> {
+        self.0
+            .iter()
+            .fold(ClientBuilder::new(), |builder, step| step(builder))
+            .build()
+    }
+}
+
 pub struct BuilderInner {
-    client: ClientBuilder,
+    client: ClientSteps,
 
     log_level: LevelFilter,
+
+    accept_invalid_certs: bool,
+
+    accept_invalid_hostnames: bool,
+
+    /// Kept to build the rustls configuration, which also checks certificate pins.
+    #[cfg(feature = "rustls")]
+    tls: TlsOptions,
+
+    built_in_root_certs: bool,
+
+    http_version: Option<HttpVersionPref>,
+
+    proxy_rules: Option<ProxyRules>,
+
+    proxy_mode: Option<ProxyMode>,
+
+    /// Kept for the redirect policy of PAC clients, reqwest's default when unset.
+    redirect_limit: usize,
+
+    /// Applied to every SOCKS proxy once the proxy configuration is complete.
+    socks_remote_dns: bool,
+
+    /// Kept to reject it with rotating proxy pools, which disable pooling.
+    pool_max_idle_per_host: Option<usize>,
+
+    /// reqwest's default unless set, after which idle origin pools are dropped.
+    pool_idle_timeout: Option<Duration>,
+
+    resolver: Option<Arc<dyn HostResolver>>,
+
+    doh: Option<DohConfig>,
+
+    /// Additional roots, also trusted by the DoH client.
+    root_certificates: Vec<Certificate>,
+
+    ip_family: Option<IpFamily>,
+
+    local_address: Option<IpAddr>,
+
+    request_timeout: Option<Duration>,
+
+    socket_path: Option<String>,
+
+    http2: Http2Settings,
+
+    runtime: RuntimeMode,
+
+    watchdog: Option<Duration>,
+
+    limiter: Limiter,
+
+    rate_limiter: RateLimiter,
 }
 
 impl BuilderInner {
     pub fn new() -> BuilderInner {
         Self {
-            client: ClientBuilder::new(),
+            client: ClientSteps::default(),
             log_level: LevelFilter::Info,
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            #[cfg(feature = "rustls")]
+            tls: TlsOptions::default(),
+            built_in_root_certs: true,
+            http_version: None,
+            proxy_rules: None,
+            proxy_mode: None,
+            redirect_limit: DEFAULT_REDIRECT_LIMIT,
+            socks_remote_dns: false,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            resolver: None,
+            doh: None,
+            root_certificates: Vec::new(),
+            ip_family: None,
+            local_address: None,
+            request_timeout: None,
+            socket_path: None,
+            http2: Http2Settings::default(),
+            runtime: RuntimeMode::default(),
+            watchdog: None,
+            limiter: Limiter::default(),
+            rate_limiter: RateLimiter::default(),
         }
     }
 }
@@ -43,6 +203,190 @@ impl Builder {
     pub fn containerize(cb: BuilderInner) -> RefCell<Builder> {
         RefCell::new(Self(Some(cb)))
     }
+
+    /// Takes the configuration out, leaving the builder unusable like `js_build` does.
+    pub(crate) fn take(&mut self) -> BuilderInner {
+        self.0.take().unwrap()
+    }
+
+    /// Reads an argument that may be either a string or a `Buffer` as bytes.
+    fn bytes_argument(cx: &mut FunctionContext, i: i32) -> NeonResult<Vec<u8>> {
+        let value = cx.argument::<JsValue>(i)?;
+
+        if value.is_a::<JsString, _>(cx) {
+            Ok(value.downcast_or_throw::<JsString, _>(cx)?.value(cx).into_bytes())
+        } else {
+            let buf = value.downcast_or_throw::<JsBuffer, _>(cx)?;
+
+            Ok(Vec::from(buf.as_slice(cx)))
+        }
+    }
+
+    /// Reads an array of names, each looked up with `find`.
+    #[cfg(feature = "rustls")]
+    fn names_argument<T>(
+        cx: &mut FunctionContext,
+        i: i32,
+        name: &str,
+        find: fn(&str) -> Option<T>,
+    ) -> NeonResult<Vec<T>> {
+        let values = cx.argument::<JsArray>(i)?.to_vec(cx)?;
+
+        let mut found = Vec::with_capacity(values.len());
+
+        for value in values {
+            let value = value.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+
+            match find(&value) {
+                Some(v) => found.push(v),
+                None => cx.throw_error(format!("Unsupported {}: {}", name, value))?,
+            }
+        }
+
+        if found.is_empty() {
+            cx.throw_error(format!("At least one {} is required", name))?;
+        }
+
+        Ok(found)
+    }
+
+    /// Reads an optional duration in seconds, `null` meaning none.
+    fn duration_argument(
+        cx: &mut FunctionContext,
+        i: i32,
+        name: &str,
+    ) -> NeonResult<Option<Duration>> {
+        let value = cx.argument::<JsValue>(i)?;
+
+        if value.is_a::<JsNull, _>(cx) {
+            return Ok(None);
+        }
+
+        let seconds = value.downcast_or_throw::<JsNumber, _>(cx)?.value(cx);
+
+        match Duration::try_from_secs_f64(seconds) {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => cx.throw_error(format!("Invalid {}: {}", name, seconds)),
+        }
+    }
+
+    /// Reads an optional positive integer limit, `null` meaning none.
+    fn limit_argument(cx: &mut FunctionContext, i: i32, name: &str) -> NeonResult<Option<usize>> {
+        let value = cx.argument::<JsValue>(i)?;
+
+        if value.is_a::<JsNull, _>(cx) {
+            return Ok(None);
+        }
+
+        let limit = value.downcast_or_throw::<JsNumber, _>(cx)?.value(cx);
+
+        if limit.fract() != 0.0 || limit < 1.0 || limit > u32::MAX as f64 {
+            return cx.throw_error(format!(
+                "Invalid {}: {}, expected a positive integer",
+                name, limit
+            ));
+        }
+
+        Ok(Some(limit as usize))
+    }
+
+    /// Follows up to `limit` redirects like `Policy::limited`, never while preconnecting, and
+    /// with PAC rules only to hosts with a PAC result.
+    fn redirect_policy(limit: usize, pac_rules: Option<Arc<ProxyRules>>) -> Policy {
+        Policy::custom(move |attempt| {
+            let pac_unresolved = pac_rules
+                .as_ref()
+                .map_or(false, |rules| !rules.pac_resolved(attempt.url()));
+
+            if limit == 0 || client::preconnecting() {
+                attempt.stop()
+            } else if attempt.previous().len() >= limit {
+                attempt.error("too many redirects")
+            } else if pac_unresolved {
+                let url = attempt.url().clone();
+
+                attempt.error(PacUnresolved { url })
+            } else {
+                attempt.follow()
+            }
+        })
+    }
+
+    fn parse_tls_version(version: &str) -> Option<Version> {
+        match version {
+            "1.0" => Some(Version::TLS_1_0),
+            "1.1" => Some(Version::TLS_1_1),
+            "1.2" => Some(Version::TLS_1_2),
+            "1.3" => Some(Version::TLS_1_3),
+            _ => None,
+        }
+    }
+
+    /// Checks that an HTTP/2 setting is an integer within `range`.
+    fn http2_setting(
+        cx: &mut FunctionContext,
+        value: Option<f64>,
+        name: &str,
+        range: RangeInclusive<u32>,
+    ) -> NeonResult<Option<u32>> {
+        match value {
+            None => Ok(None),
+            Some(v)
+                if v.fract() == 0.0 && v >= *range.start() as f64 && v <= *range.end() as f64 =>
+            {
+                Ok(Some(v as u32))
+            }
+            Some(v) => cx.throw_error(format!(
+                "Invalid {}: {}, expected an integer from {} to {}",
+                name,
+                v,
+                range.start(),
+                range.end()
+            )),
+        }
+    }
+
+    /// Restricts the HTTP version, throwing if another option already restricted it differently.
+    fn set_http_version(cx: &mut FunctionContext, pref: HttpVersionPref) -> JsResult<BoxedBuilder> {
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        if let Some(current) = rm.0.as_ref().unwrap().http_version {
+            if current != pref {
+                return cx.throw_error(format!(
+                    "Conflicting HTTP version options: {:?} and {:?}",
+                    current, pref
+                ));
+            }
+        }
+
+        let mut cb = rm.0.take().unwrap();
+        cb.http_version = Some(pref);
+
+        Ok(JsBox::new(cx, Self::containerize(cb)))
+    }
+
+    /// Splits a PEM bundle into its certificates, or returns the input as a single DER certificate.
+    fn parse_certificates(data: &[u8]) -> reqwest::Result<Vec<Certificate>> {
+        const END: &[u8] = b"-----END CERTIFICATE-----";
+
+        if !data.windows(END.len()).any(|w| w == END) {
+            return Ok(vec![Certificate::from_der(data)?]);
+        }
+
+        let mut certs = Vec::new();
+        let mut rest = data;
+
+        while let Some(pos) = rest.windows(END.len()).position(|w| w == END) {
+            let (pem, tail) = rest.split_at(pos + END.len());
+
+            certs.push(Certificate::from_pem(pem)?);
+            rest = tail;
+        }
+
+        Ok(certs)
+    }
 }
 
 /// Neon bindings for `Builder`.
@@ -59,9 +403,9 @@ impl Builder {
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
-        cb.client = cb
-            .client
-            .connect_timeout(std::time::Duration::from_secs(duration_seconds as u64));
+        cb.client = cb.client.then(move |b| {
+            b.connect_timeout(std::time::Duration::from_secs(duration_seconds as u64))
+        });
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
@@ -74,9 +418,10 @@ impl Builder {
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
+        cb.request_timeout = Some(std::time::Duration::from_secs(duration_seconds as u64));
         cb.client = cb
             .client
-            .timeout(std::time::Duration::from_secs(duration_seconds as u64));
+            .then(move |b| b.timeout(std::time::Duration::from_secs(duration_seconds as u64)));
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
@@ -89,7 +434,7 @@ impl Builder {
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
-        cb.client = cb.client.https_only(only);
+        cb.client = cb.client.then(move |b| b.https_only(only));
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
@@ -102,7 +447,7 @@ impl Builder {
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
-        cb.client = cb.client.strip_sensitive_headers(strip);
+        cb.client = cb.client.then(move |b| b.strip_sensitive_headers(strip));
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
@@ -115,105 +460,1262 @@ impl Builder {
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
+        cb.redirect_limit = limit;
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_http2_adaptive_window(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let enabled = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.http2.adaptive_window = enabled;
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// SETTINGS_INITIAL_WINDOW_SIZE, validated by `js_build`.
+    pub fn js_http2_initial_stream_window_size(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let value = cx.argument::<JsNumber>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.http2.stream_window = Some(value);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// Connection window announced with the first WINDOW_UPDATE, validated by `js_build`.
+    pub fn js_http2_initial_connection_window_size(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let value = cx.argument::<JsNumber>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.http2.connection_window = Some(value);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// SETTINGS_MAX_FRAME_SIZE, validated by `js_build`.
+    pub fn js_http2_max_frame_size(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let value = cx.argument::<JsNumber>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.http2.max_frame_size = Some(value);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_pool_max_idle_per_host(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let max = cx.argument::<JsNumber>(0)?.value(&mut cx);
+
+        if max.is_nan() || max < 0.0 {
+            cx.throw_error(format!("Invalid pool max idle per host: {}", max))?;
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.pool_max_idle_per_host(max as usize));
+        cb.pool_max_idle_per_host = Some(max as usize);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// Allows `requests` per `interval` seconds to each origin matching a pattern, up to `burst` at once.
+    pub fn js_rate_limit(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let origin = cx.argument::<JsString>(0)?.value(&mut cx);
+        let requests = cx.argument::<JsNumber>(1)?.value(&mut cx);
+        let interval = cx.argument::<JsNumber>(2)?.value(&mut cx);
+        let burst = cx.argument::<JsNumber>(3)?.value(&mut cx);
+
+        let pattern = match OriginPattern::parse(&origin) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(e)?,
+        };
+
+        if !(requests.is_finite() && requests > 0.0) {
+            cx.throw_error(format!("Invalid rate limit requests: {}", requests))?;
+        }
 
-        let policy = match limit {
-            0 => Policy::none(),
-            _ => Policy::limited(limit),
+        let interval = match Duration::try_from_secs_f64(interval) {
+            Ok(v) if !v.is_zero() => v,
+            _ => cx.throw_error(format!("Invalid rate limit interval: {}", interval))?,
         };
 
-        cb.client = cb.client.redirect(policy);
+        if burst.fract() != 0.0 || !(1.0..=u32::MAX as f64).contains(&burst) {
+            cx.throw_error(format!("Invalid rate limit burst: {}, expected a positive integer", burst))?;
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.rate_limiter.push(RateLimit::new(pattern, requests, interval, burst));
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
 
-    pub fn js_http2_adaptive_window(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
-        let enabled = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+    /// Limits requests in flight globally and per origin, `null` meaning unlimited.
+    pub fn js_concurrency_limits(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let max = Self::limit_argument(&mut cx, 0, "max concurrent requests")?;
+        let max_per_origin = Self::limit_argument(&mut cx, 1, "max concurrent requests per origin")?;
+        let order = cx.argument::<JsString>(2)?.value(&mut cx);
+
+        let order = match order.parse::<QueueOrder>() {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(e)?,
+        };
 
         let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
 
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
-        cb.client = cb.client.http2_adaptive_window(enabled);
+        cb.limiter = Limiter::new(max, max_per_origin, order);
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
 
-    pub fn js_proxy(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
-        let proxy = cx.argument::<JsString>(0)?.value(&mut cx);
+    /// Fails requests that have not settled after the given seconds, queueing and retries included.
+    pub fn js_watchdog_timeout(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let timeout = Self::duration_argument(&mut cx, 0, "watchdog timeout")?;
 
         let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
 
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
-        cb.client = cb.client.proxy(Proxy::all(proxy).unwrap());
+        cb.watchdog = timeout;
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
 
-    pub fn js_log_level(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
-        let level = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+    /// Idle pooled connections are closed after the timeout, never with `null`.
+    pub fn js_pool_idle_timeout(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let timeout = Self::duration_argument(&mut cx, 0, "pool idle timeout")?;
 
         let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
 
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.pool_idle_timeout(timeout));
+        cb.pool_idle_timeout = timeout;
 
-        cb.log_level = match level {
-            0 => LevelFilter::Off,
-            1 => LevelFilter::Error,
-            2 => LevelFilter::Warn,
-            3 => LevelFilter::Info,
-            4 => LevelFilter::Debug,
-            5 => {
-                cb.client = cb.client.connection_verbose(true);
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
 
-                LevelFilter::Trace
-            }
+    pub fn js_tcp_keepalive(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let interval = Self::duration_argument(&mut cx, 0, "TCP keepalive")?;
 
-            _ => LevelFilter::Info,
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.tcp_keepalive(interval));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_tcp_nodelay(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let enabled = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.tcp_nodelay(enabled));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_http2_keep_alive_interval(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let interval = Self::duration_argument(&mut cx, 0, "HTTP/2 keep-alive interval")?;
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.http2_keep_alive_interval(interval));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_http2_keep_alive_timeout(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let timeout = match Self::duration_argument(&mut cx, 0, "HTTP/2 keep-alive timeout")? {
+            Some(v) => v,
+            None => cx.throw_error("HTTP/2 keep-alive timeout is required")?,
         };
 
-        // Since texts only have one global rust-fetch instance,
-        // a global logger instance should be fine, also needed to capture connection verbose.
-        LoggerBuilder::new().filter_level(cb.log_level).try_init().ok();
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.http2_keep_alive_timeout(timeout));
 
         Ok(JsBox::new(&mut cx, Self::containerize(cb)))
     }
 
-    pub fn js_build(mut cx: FunctionContext) -> JsResult<JsBox<Client>> {
+    pub fn js_http2_keep_alive_while_idle(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let enabled = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
         let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
 
         let mut rm = boxed.borrow_mut();
 
         let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.http2_keep_alive_while_idle(enabled));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
 
-        cb.client = cb.client.http2_initial_stream_window_size(1024 * 256 * 24);
+    pub fn js_min_tls_version(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let version = cx.argument::<JsString>(0)?.value(&mut cx);
 
-        let time_jar = std::sync::Arc::new(TimeJar::default());
+        let version = match Self::parse_tls_version(&version) {
+            Some(v) => v,
+            None => cx.throw_error(format!("Invalid TLS version: {}", version))?,
+        };
 
-        cb.client = cb.client.cookie_provider(time_jar.clone());
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
 
-        match env::var("NODE_TLS_REJECT_UNAUTHORIZED") {
-            Ok(value) => {
-                if value == "0" {
-                    cb.client = cb.client.danger_accept_invalid_certs(true);
-                }
-            },
-            Err(_) => {}
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.min_tls_version(version));
+
+        #[cfg(feature = "rustls")]
+        {
+            cb.tls.min_version = Some(version);
         }
 
-        let client = cb.client.build().unwrap();
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
 
-        Ok(JsBox::new(
-            &mut cx,
-            Client {
-                runtime: Runtime::new().unwrap(),
-                client,
-                time_jar,
-            },
-        ))
+    pub fn js_max_tls_version(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let version = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let version = match Self::parse_tls_version(&version) {
+            Some(v) => v,
+            None => cx.throw_error(format!("Invalid TLS version: {}", version))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.max_tls_version(version));
+
+        #[cfg(feature = "rustls")]
+        {
+            cb.tls.max_version = Some(version);
+        }
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_http1_only(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        Self::set_http_version(&mut cx, HttpVersionPref::Http1)
+    }
+
+    pub fn js_http2_prior_knowledge(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        Self::set_http_version(&mut cx, HttpVersionPref::Http2)
+    }
+
+    /// reqwest only offers `h2` and `http/1.1`, so lists map onto the HTTP version restriction.
+    pub fn js_alpn_protocols(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let protocols = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+
+        let mut names = Vec::with_capacity(protocols.len());
+
+        for protocol in protocols {
+            names.push(protocol.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx));
+        }
+
+        let pref = match names.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["h2", "http/1.1"] => None,
+            ["http/1.1"] => Some(HttpVersionPref::Http1),
+            ["h2"] => Some(HttpVersionPref::Http2),
+            _ => cx.throw_error(format!("Unsupported ALPN protocols: {:?}", names))?,
+        };
+
+        match pref {
+            Some(pref) => Self::set_http_version(&mut cx, pref),
+            None => {
+                let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+                let mut rm = boxed.borrow_mut();
+
+                let cb = rm.0.take().unwrap();
+
+                Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+            }
+        }
+    }
+
+    pub fn js_proxy(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let proxy = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let proxy = match ProxyRules::parse_proxy(&proxy, None) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(e)?,
+        };
+
+        let mut cb = rm.0.take().unwrap();
+        cb.proxy_rules = Some(ProxyRules {
+            all: Some(proxy),
+            ..Default::default()
+        });
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_accept_invalid_certs(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let accept = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.accept_invalid_certs = accept;
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_accept_invalid_hostnames(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let accept = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.accept_invalid_hostnames = accept;
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_proxy_mode(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let mode = cx.argument::<JsString>(0)?.value(&mut cx);
+        let env = cx.argument::<JsObject>(1)?;
+
+        let mode = match mode.as_str() {
+            "none" => ProxyMode::None,
+            "env" => {
+                let env = Client::map_jsobject(&mut cx, &env)?;
+
+                match ProxyRules::from_env(&env) {
+                    Ok(v) => ProxyMode::Env(v),
+                    Err(e) => cx.throw_error(format!("Invalid proxy environment: {}", e))?,
+                }
+            }
+            "explicit" => ProxyMode::Explicit,
+            _ => cx.throw_error(format!("Invalid proxy mode: {}", mode))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.proxy_mode = Some(mode);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_pac(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.proxy_rules = Some(ProxyRules {
+            pac: Some(Default::default()),
+            ..Default::default()
+        });
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_proxy_pool(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let proxies = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+        let rotation = cx.argument::<JsString>(1)?.value(&mut cx);
+        let cooldown = cx.argument::<JsNumber>(2)?.value(&mut cx);
+
+        let rotation = match rotation.parse::<Rotation>() {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(e)?,
+        };
+
+        if proxies.is_empty() {
+            cx.throw_error("Proxy pool requires at least one proxy")?;
+        }
+
+        if !cooldown.is_finite() || cooldown < 0.0 {
+            cx.throw_error(format!("Invalid proxy failover cooldown: {}", cooldown))?;
+        }
+
+        let mut urls = Vec::with_capacity(proxies.len());
+
+        for proxy in proxies {
+            let proxy = proxy.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+
+            match ProxyRules::parse_proxy(&proxy, None) {
+                Ok(v) => urls.push(v),
+                Err(e) => cx.throw_error(e)?,
+            }
+        }
+
+        let cooldown = match Duration::try_from_secs_f64(cooldown) {
+            Ok(v) => v,
+            Err(_) => cx.throw_error(format!("Invalid proxy pool failover cooldown: {}", cooldown))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.proxy_rules = Some(ProxyRules {
+            pool: Some(ProxyPool::new(urls, rotation, cooldown)),
+            ..Default::default()
+        });
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_socks_remote_dns(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let enabled = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.socks_remote_dns = enabled;
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_proxy_config(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let config = cx.argument::<JsObject>(0)?;
+
+        let mut rules = ProxyRules::default();
+
+        let username = config
+            .get_opt::<JsString, _, _>(&mut cx, "username")?
+            .map(|v| v.value(&mut cx));
+        let password = config
+            .get_opt::<JsString, _, _>(&mut cx, "password")?
+            .map(|v| v.value(&mut cx));
+
+        let credentials = username
+            .as_deref()
+            .map(|username| (username, password.as_deref().unwrap_or("")));
+
+        let remote_dns = config
+            .get_opt::<JsBoolean, _, _>(&mut cx, "socksRemoteDns")?
+            .map_or(false, |v| v.value(&mut cx));
+
+        for (key, target) in [
+            ("http", &mut rules.http),
+            ("https", &mut rules.https),
+            ("all", &mut rules.all),
+        ] {
+            if let Some(proxy) = config.get_opt::<JsString, _, _>(&mut cx, key)? {
+                let proxy = proxy.value(&mut cx);
+
+                let proxy = match ProxyRules::parse_proxy(&proxy, credentials) {
+                    Ok(v) => v,
+                    Err(e) => cx.throw_error(e)?,
+                };
+
+                *target = Some(proxy);
+            }
+        }
+
+        if remote_dns {
+            rules.set_socks_remote_dns();
+        }
+
+        if let Some(entries) = config.get_opt::<JsArray, _, _>(&mut cx, "noProxy")? {
+            for entry in entries.to_vec(&mut cx)? {
+                let entry = entry.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+
+                match Bypass::parse(&entry) {
+                    Ok(v) => rules.bypass.push(v),
+                    Err(e) => cx.throw_error(e)?,
+                }
+            }
+        }
+
+        if let Some(headers) = config.get_opt::<JsObject, _, _>(&mut cx, "headers")? {
+            let headers = Client::map_jsobject(&mut cx, &headers)?;
+
+            let headers: HeaderMap = match (&headers).try_into() {
+                Ok(v) => v,
+                Err(e) => cx.throw_error(format!("Invalid proxy headers: {}", e))?,
+            };
+
+            // Sent in CONNECT requests by the bridge, which only tunnels through http:// proxies.
+            let tls_proxy = [&rules.http, &rules.https, &rules.all]
+                .iter()
+                .any(|proxy| matches!(proxy, Some(v) if v.scheme() == "https"));
+
+            if tls_proxy {
+                cx.throw_error("Proxy headers can only be sent to http:// proxies")?;
+            }
+
+            rules.bridge = ProxyBridge::with_headers(headers);
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.proxy_rules = Some(rules);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// Static host overrides, `{ hostname: addresses[] }`.
+    ///
+    /// hyper connects to the port of the request URL, so addresses are IPs without a port.
+    pub fn js_hosts(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let hosts = cx.argument::<JsObject>(0)?;
+
+        let mut overrides = Vec::new();
+
+        for name in hosts.get_own_property_names(&mut cx)?.to_vec(&mut cx)? {
+            let name = name.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+
+            let addresses = hosts.get::<JsArray, _, _>(&mut cx, name.as_str())?.to_vec(&mut cx)?;
+
+            let mut addrs = Vec::with_capacity(addresses.len());
+
+            for address in addresses {
+                let address = address.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+
+                match address.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+                    Ok(ip) => addrs.push(SocketAddr::new(ip, 0)),
+                    Err(_) => cx.throw_error(format!("Invalid address for {}: {}", name, address))?,
+                }
+            }
+
+            overrides.push((name.to_ascii_lowercase(), addrs));
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+
+        for (name, addrs) in overrides {
+            cb.client = cb.client.then(move |b| b.resolve_to_addrs(&name, &addrs));
+        }
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_dns_resolver(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let lookup = cx.argument::<JsFunction>(0)?;
+
+        let resolver = JsResolver::new(&mut cx, lookup);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.resolver = Some(Arc::new(resolver));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_doh(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let url = cx.argument::<JsString>(0)?.value(&mut cx);
+        let bootstrap = cx.argument::<JsArray>(1)?.to_vec(&mut cx)?;
+        let fallback = cx.argument::<JsBoolean>(2)?.value(&mut cx);
+        let timeout = cx.argument::<JsNumber>(3)?.value(&mut cx);
+
+        let url = match Url::parse(&url) {
+            Ok(v) if v.scheme() == "https" && v.has_host() => v,
+            _ => cx.throw_error(format!("Invalid DoH URL: {}", url))?,
+        };
+
+        let mut addrs = Vec::with_capacity(bootstrap.len());
+
+        for address in bootstrap {
+            let address = address.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+
+            match address.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+                Ok(v) => addrs.push(v),
+                Err(_) => cx.throw_error(format!("Invalid DoH bootstrap address: {}", address))?,
+            }
+        }
+
+        let timeout = match Duration::try_from_secs_f64(timeout) {
+            Ok(v) if !v.is_zero() => v,
+            _ => cx.throw_error(format!("Invalid DoH timeout: {}", timeout))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.doh = Some(DohConfig {
+            url,
+            bootstrap: addrs,
+            fallback,
+            timeout,
+        });
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_ip_family(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let family = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let family = match family.parse::<IpFamily>() {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(e)?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.ip_family = Some(family);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_local_address(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let address = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let address = match address.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(v) => v,
+            Err(_) => cx.throw_error(format!("Invalid local address: {}", address))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.local_address = Some(address);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// Sends every request over the Unix socket at the given path.
+    pub fn js_socket_path(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let path = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        if cfg!(not(unix)) {
+            cx.throw_error("Unix sockets are not supported on this platform")?;
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.socket_path = Some(path);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// Selects the runtime requests run on, worker threads only applying to a dedicated runtime.
+    pub fn js_runtime(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let mode = cx.argument::<JsString>(0)?.value(&mut cx);
+        let threads = cx.argument::<JsValue>(1)?;
+
+        let mut mode = match mode.parse::<RuntimeMode>() {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(e)?,
+        };
+
+        if !threads.is_a::<JsNull, _>(&mut cx) {
+            let threads = threads.downcast_or_throw::<JsNumber, _>(&mut cx)?.value(&mut cx);
+
+            if !matches!(mode, RuntimeMode::Dedicated(_)) {
+                cx.throw_error("Worker threads can only be set for a dedicated runtime")?;
+            }
+
+            if threads.fract() != 0.0 || !(1.0..=1024.0).contains(&threads) {
+                cx.throw_error(format!(
+                    "Invalid worker threads: {}, expected an integer from 1 to 1024",
+                    threads
+                ))?;
+            }
+
+            mode = RuntimeMode::Dedicated(threads as usize);
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.runtime = mode;
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_root_certificate(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let data = Self::bytes_argument(&mut cx, 0)?;
+
+        let certs = match Self::parse_certificates(&data) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid root certificate: {}", e))?,
+        };
+
+        #[cfg(feature = "rustls")]
+        let roots = match tls::parse_certificates(&data) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid root certificate: {}", e))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+
+        for cert in certs {
+            let root = cert.clone();
+
+            cb.client = cb.client.then(move |b| b.add_root_certificate(root.clone()));
+            cb.root_certificates.push(cert);
+        }
+
+        #[cfg(feature = "rustls")]
+        cb.tls.roots.extend(roots);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_tls_built_in_root_certs(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let enabled = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.built_in_root_certs = enabled;
+        cb.client = cb.client.then(move |b| b.tls_built_in_root_certs(enabled));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_identity_pkcs12(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let der = Self::bytes_argument(&mut cx, 0)?;
+        let password = cx.argument::<JsString>(1)?.value(&mut cx);
+
+        #[cfg(feature = "native-tls")]
+        let identity = Identity::from_pkcs12_der(&der, &password);
+
+        #[cfg(not(feature = "native-tls"))]
+        let identity: reqwest::Result<Identity> = {
+            let _ = (der, password);
+
+            cx.throw_error("PKCS#12 identities require the native-tls backend")?
+        };
+
+        let identity = match identity {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid PKCS#12 identity: {}", e))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.client = cb.client.then(move |b| b.identity(identity.clone()));
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_identity_pem(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let cert = Self::bytes_argument(&mut cx, 0)?;
+        let key = Self::bytes_argument(&mut cx, 1)?;
+
+        #[cfg(feature = "native-tls")]
+        let identity = Identity::from_pkcs8_pem(&cert, &key).map_err(|e| e.to_string());
+
+        // rustls reads the key and certificate chain from a single PEM buffer.
+        #[cfg(feature = "rustls")]
+        let identity = tls::parse_identity(&[key, cert].join(&b'\n'));
+
+        let identity = match identity {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid PEM identity: {}", e))?,
+        };
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+
+        #[cfg(feature = "native-tls")]
+        {
+            cb.client = cb.client.then(move |b| b.identity(identity.clone()));
+        }
+
+        #[cfg(feature = "rustls")]
+        {
+            cb.tls.identity = Some(identity);
+        }
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// ClientHello options only apply to rustls, native-tls builds it from the platform library.
+    #[cfg(not(feature = "rustls"))]
+    pub fn js_tls_cipher_suites(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        cx.throw_error("TLS cipher suites require the rustls backend")
+    }
+
+    #[cfg(feature = "rustls")]
+    pub fn js_tls_cipher_suites(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let values = Self::names_argument(&mut cx, 0, "cipher suite", tls::cipher_suite)?;
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.tls.cipher_suites = Some(values);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    #[cfg(not(feature = "rustls"))]
+    pub fn js_tls_key_exchange_groups(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        cx.throw_error("TLS key exchange groups require the rustls backend")
+    }
+
+    #[cfg(feature = "rustls")]
+    pub fn js_tls_key_exchange_groups(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let values = Self::names_argument(&mut cx, 0, "key exchange group", tls::kx_group)?;
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.tls.kx_groups = Some(values);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    #[cfg(not(feature = "rustls"))]
+    pub fn js_tls_signature_algorithms(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        cx.throw_error("TLS signature algorithms require the rustls backend")
+    }
+
+    #[cfg(feature = "rustls")]
+    pub fn js_tls_signature_algorithms(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let values = Self::names_argument(&mut cx, 0, "signature algorithm", tls::signature_scheme)?;
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.tls.signature_schemes = Some(values);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    /// native-tls exposes no certificate verification hook, pins are checked by rustls only.
+    #[cfg(not(feature = "rustls"))]
+    pub fn js_certificate_pins(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        cx.throw_error("Certificate pinning requires the rustls backend")
+    }
+
+    #[cfg(feature = "rustls")]
+    pub fn js_certificate_pins(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let hosts = cx.argument::<JsObject>(0)?;
+        let report_only = cx.argument::<JsBoolean>(1)?.value(&mut cx);
+
+        let mut pins = Pins::new(report_only);
+
+        let names = hosts.get_own_property_names(&mut cx)?;
+
+        for i in 0..names.len(&mut cx) {
+            let host: Handle<JsString> = names.get(&mut cx, i)?;
+            let host = host.value(&mut cx);
+
+            let values: Handle<JsArray> = hosts.get(&mut cx, host.as_str())?;
+
+            for value in values.to_vec(&mut cx)? {
+                let pin = value.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+
+                if let Err(e) = pins.add(&host, &pin) {
+                    cx.throw_error(e)?;
+                }
+            }
+        }
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+        cb.tls.pins = Some(pins);
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_log_level(mut cx: FunctionContext) -> JsResult<BoxedBuilder> {
+        let level = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+
+        cb.log_level = match level {
+            0 => LevelFilter::Off,
+            1 => LevelFilter::Error,
+            2 => LevelFilter::Warn,
+            3 => LevelFilter::Info,
+            4 => LevelFilter::Debug,
+            5 => {
+                cb.client = cb.client.then(|b| b.connection_verbose(true));
+
+                LevelFilter::Trace
+            }
+
+            _ => LevelFilter::Info,
+        };
+
+        // Since texts only have one global rust-fetch instance,
+        // a global logger instance should be fine, also needed to capture connection verbose.
+        LoggerBuilder::new().filter_level(cb.log_level).try_init().ok();
+
+        Ok(JsBox::new(&mut cx, Self::containerize(cb)))
+    }
+
+    pub fn js_build(mut cx: FunctionContext) -> JsResult<JsBox<Client>> {
+        let boxed = cx.this().downcast_or_throw::<BoxedBuilder, _>(&mut cx)?;
+
+        let mut rm = boxed.borrow_mut();
+
+        let mut cb = rm.0.take().unwrap();
+
+        let time_jar = Arc::new(TimeJar::default());
+
+        let socket_path = cb.socket_path.take();
+
+        let request_timeout = cb.request_timeout;
+
+        let redirect_limit = cb.redirect_limit;
+
+        let watchdog = cb.watchdog;
+
+        let limiter = Arc::new(std::mem::take(&mut cb.limiter));
+
+        let rate_limiter = Arc::new(std::mem::take(&mut cb.rate_limiter));
+
+        let pool_idle_timeout = cb.pool_idle_timeout;
+
+        let runtime_mode = cb.runtime;
+
+        let (client, client_steps, proxy_rules) = Self::build_client(&mut cx, cb, time_jar.clone())?;
+
+        // Started once the configuration is known to be valid, so invalid ones leave no threads behind.
+        let runtime = match ClientRuntime::new(runtime_mode) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Could not start the runtime: {}", e))?,
+        };
+
+        Ok(JsBox::new(
+            &mut cx,
+            Client {
+                runtime,
+                pools: Arc::new(OriginPools::new(client_steps, client, pool_idle_timeout)),
+                time_jar,
+                proxy_rules,
+                #[cfg(unix)]
+                unix_client: RwLock::new(HyperClient::builder().build(UnixConnector)),
+                socket_path,
+                request_timeout,
+                redirect_limit,
+                lifecycle: Arc::new(Lifecycle::new()),
+                watchdog,
+                limiter,
+                rate_limiter,
+            },
+        ))
+    }
+
+    /// Builds the reqwest client, returning its steps to build it again with an empty pool.
+    fn build_client(
+        cx: &mut FunctionContext,
+        mut cb: BuilderInner,
+        time_jar: Arc<TimeJar>,
+    ) -> NeonResult<(ReqwestClient, ClientSteps, Option<Arc<ProxyRules>>)> {
+        let http2 = std::mem::take(&mut cb.http2);
+
+        let stream_window = Self::http2_setting(
+            cx,
+            http2.stream_window,
+            "HTTP/2 initial stream window size",
+            1..=HTTP2_MAX_WINDOW_SIZE,
+        )?;
+        let connection_window = Self::http2_setting(
+            cx,
+            http2.connection_window,
+            "HTTP/2 initial connection window size",
+            1..=HTTP2_MAX_WINDOW_SIZE,
+        )?;
+        let max_frame_size = Self::http2_setting(
+            cx,
+            http2.max_frame_size,
+            "HTTP/2 max frame size",
+            HTTP2_FRAME_SIZES,
+        )?;
+
+        // hyper ignores fixed windows once the adaptive window is enabled.
+        if http2.adaptive_window && (stream_window.is_some() || connection_window.is_some()) {
+            cx.throw_error("The HTTP/2 adaptive window cannot be combined with fixed window sizes")?;
+        }
+
+        cb.client = cb.client.then(move |b| {
+            b.http2_adaptive_window(http2.adaptive_window)
+                .http2_initial_stream_window_size(stream_window.unwrap_or(HTTP2_DEFAULT_STREAM_WINDOW))
+                .http2_initial_connection_window_size(connection_window)
+                .http2_max_frame_size(max_frame_size)
+        });
+
+        cb.client = cb.client.then(move |b| b.cookie_provider(time_jar.clone()));
+
+        if let Ok(value) = env::var("NODE_TLS_REJECT_UNAUTHORIZED") {
+            if value == "0" {
+                warn!("NODE_TLS_REJECT_UNAUTHORIZED is set to 0");
+                cb.accept_invalid_certs = true;
+            }
+        }
+
+        // Warned at build time, as the logger is only initialized with the log level option.
+        if cb.accept_invalid_certs {
+            warn!("TLS certificate verification is disabled, any certificate will be trusted");
+            cb.client = cb.client.then(|b| b.danger_accept_invalid_certs(true));
+        }
+
+        if cb.accept_invalid_hostnames {
+            #[cfg(feature = "native-tls")]
+            {
+                warn!("TLS hostname verification is disabled, certificates for any host will be trusted");
+                cb.client = cb.client.then(|b| b.danger_accept_invalid_hostnames(true));
+            }
+
+            #[cfg(not(feature = "native-tls"))]
+            cx.throw_error("acceptInvalidHostnames requires the native-tls backend")?;
+        }
+
+        // rustls has no built-in roots with manual roots, load the platform's instead.
+        #[cfg(feature = "rustls")]
+        if cb.built_in_root_certs {
+            match rustls_native_certs::load_native_certs() {
+                Ok(certs) => {
+                    for cert in certs {
+                        match Certificate::from_der(&cert.0) {
+                            Ok(root) => {
+                                let native = root.clone();
+
+                                cb.client = cb.client.then(move |b| b.add_root_certificate(native.clone()));
+                                cb.root_certificates.push(root);
+                                cb.tls.roots.push(rustls::Certificate(cert.0));
+                            }
+                            Err(e) => warn!("Skipping invalid native root certificate: {}", e),
+                        }
+                    }
+                }
+                Err(e) => warn!("Could not load native root certificates: {}", e),
+            }
+        }
+
+        cb.client = match cb.http_version {
+            Some(HttpVersionPref::Http1) => cb.client.then(|b| b.http1_only()),
+            Some(HttpVersionPref::Http2) => cb.client.then(|b| b.http2_prior_knowledge()),
+            None => cb.client,
+        };
+
+        #[cfg(feature = "rustls")]
+        {
+            let tls = std::mem::take(&mut cb.tls);
+
+            match tls.client_config(cb.accept_invalid_certs, cb.http_version) {
+                Ok(config) => {
+                    cb.client = cb.client.then(move |b| b.use_preconfigured_tls(config.clone()))
+                }
+                Err(e) => cx.throw_error(format!("Invalid TLS configuration: {}", e))?,
+            }
+        }
+
+        if let Some(doh) = cb.doh.take() {
+            if cb.resolver.is_some() {
+                cx.throw_error("Only one DNS resolver can be configured")?;
+            }
+
+            // Queries connect directly, a proxy could not be resolved through DoH itself.
+            let mut client = ReqwestClient::builder()
+                .no_proxy()
+                .timeout(doh.timeout)
+                .tls_built_in_root_certs(cb.built_in_root_certs)
+                .danger_accept_invalid_certs(cb.accept_invalid_certs);
+
+            if let (Some(host), false) = (doh.url.host_str(), doh.bootstrap.is_empty()) {
+                let addrs: Vec<SocketAddr> = doh
+                    .bootstrap
+                    .iter()
+                    .map(|ip| SocketAddr::new(*ip, 0))
+                    .collect();
+
+                client = client.resolve_to_addrs(host, &addrs);
+            }
+
+            for cert in &cb.root_certificates {
+                client = client.add_root_certificate(cert.clone());
+            }
+
+            let client = match client.build() {
+                Ok(v) => v,
+                Err(e) => cx.throw_error(format!("Invalid DoH configuration: {}", e))?,
+            };
+
+            cb.resolver = Some(Arc::new(DohResolver::new(client, doh.url, doh.fallback)));
+        }
+
+        if let Some(family) = cb.ip_family {
+            if matches!(cb.local_address, Some(ip) if !family.matches(&ip)) {
+                cx.throw_error("Local address does not match the IP family")?;
+            }
+
+            let inner = cb.resolver.take().unwrap_or_else(|| Arc::new(SystemResolver));
+
+            cb.resolver = Some(Arc::new(FamilyFilter::new(inner, family)));
+        }
+
+        if let Some(ip) = cb.local_address {
+            cb.client = cb.client.then(move |b| b.local_address(ip));
+        }
+
+        // The cache is not carried over when the pool is reset, as answers may be stale as well.
+        if let Some(resolver) = cb.resolver.take() {
+            cb.client = cb
+                .client
+                .then(move |b| b.dns_resolver(Arc::new(CachingResolver::new(resolver.clone()))));
+        }
+
+        // Without a mode reqwest's own system proxy detection stays in effect for clients
+        // without a proxy, which is then not known here for logs and response metadata.
+        let has_proxy = cb.proxy_rules.is_some();
+
+        match (cb.proxy_mode.take(), has_proxy) {
+            (Some(ProxyMode::None), false) => {
+                cb.client = cb.client.then(|b| b.no_proxy());
+                cb.proxy_rules = Some(ProxyRules::default());
+            }
+            (Some(ProxyMode::Env(rules)), false) => {
+                cb.proxy_rules = Some(rules);
+            }
+            (Some(ProxyMode::Explicit), false) => {
+                cx.throw_error("Proxy mode explicit requires a proxy")?;
+            }
+            (Some(ProxyMode::None), true) | (Some(ProxyMode::Env(_)), true) => {
+                cx.throw_error("A proxy can only be configured with the explicit proxy mode")?;
+            }
+            (Some(ProxyMode::Explicit), true) | (None, _) => {}
+        }
+
+        if cb.socks_remote_dns {
+            if let Some(rules) = cb.proxy_rules.as_mut() {
+                rules.set_socks_remote_dns();
+            }
+        }
+
+        // Connections are pooled per origin rather than per proxy, so a reused connection
+        // would go through whichever proxy opened it.
+        let rotates = cb
+            .proxy_rules
+            .as_ref()
+            .and_then(|rules| rules.pool.as_ref())
+            .map_or(false, |pool| pool.rotation() != Rotation::Sticky);
+
+        if rotates {
+            if cb.pool_max_idle_per_host.map_or(false, |max| max > 0) {
+                cx.throw_error(
+                    "poolMaxIdlePerHost cannot be combined with a round-robin or random proxy pool",
+                )?;
+            }
+
+            cb.client = cb.client.then(|b| b.pool_max_idle_per_host(0));
+        }
+
+        let proxy_rules = cb.proxy_rules.map(Arc::new);
+
+        let limit = cb.redirect_limit;
+        let pac_rules = proxy_rules.clone().filter(|rules| rules.pac.is_some());
+
+        // Policies cannot be cloned, each build gets its own.
+        cb.client = cb
+            .client
+            .then(move |b| b.redirect(Self::redirect_policy(limit, pac_rules.clone())));
+
+        if let Some(rules) = proxy_rules.clone() {
+            let proxy = Proxy::custom(move |url| {
+                rules.resolve(url).map(|proxy| rules.bridge.route(proxy))
+            });
+
+            cb.client = cb.client.then(move |b| b.proxy(proxy.clone()));
+        }
+
+        let client = match cb.client.build() {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid client configuration: {}", e))?,
+        };
+
+        Ok((client, cb.client, proxy_rules))
     }
 }
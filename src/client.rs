@@ -1,33 +1,162 @@
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::fmt;
+use std::future;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::error::Error as StdError;
 use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 
+use tokio::task::JoinError;
+
 use futures_retry::{ErrorHandler, FutureRetry, RetryPolicy};
 
-use log::{debug, trace};
+use log::{debug, error, trace};
 use neon::prelude::*;
 use neon::types::buffer::TypedArray;
 
-use tokio::runtime::Runtime;
-
 use reqwest::header::HeaderMap;
-use reqwest::{Body, Client as ReqwestClient, Error, Method, Response};
-
+use reqwest::{Body, Error, Method, Response, Url};
+
+#[cfg(unix)]
+use reqwest::Request;
+
+#[cfg(unix)]
+use std::io::Read;
+
+#[cfg(unix)]
+use reqwest::cookie::CookieStore;
+#[cfg(unix)]
+use reqwest::StatusCode;
+
+#[cfg(unix)]
+use hyper::header::{
+    HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HOST, LOCATION,
+    RANGE, SET_COOKIE, TRANSFER_ENCODING,
+};
+#[cfg(unix)]
+use hyper::Client as HyperClient;
+
+use crate::lifecycle::Lifecycle;
+use crate::limiter::{Counts, Limiter};
+use crate::pool::OriginPools;
+#[cfg(feature = "rustls")]
+use crate::pinning::PinError;
+use crate::proxy::{PacUnresolved, ProxyRules, Rotation};
+use crate::rate_limit::RateLimiter;
+use crate::runtime::ClientRuntime;
 use crate::time_jar::{TimeJar, NewCookies};
+#[cfg(unix)]
+use crate::unix::{self, UnixConnector};
 
 pub const RETRY_DURATION: Duration = Duration::from_millis(200);
 
+tokio::task_local! {
+    /// Set while polling a preconnect request, which follows no redirects and stores no cookies.
+    static PRECONNECT: ();
+}
+
+/// Whether the request being polled is a preconnect.
+pub fn preconnecting() -> bool {
+    PRECONNECT.try_with(|_| ()).is_ok()
+}
+
 pub struct Client {
-    pub(crate) runtime: Runtime,
+    pub(crate) runtime: ClientRuntime,
 
-    pub(crate) client: ReqwestClient,
+    /// reqwest clients per origin, replaced to close their pools.
+    pub(crate) pools: Arc<OriginPools>,
 
     pub(crate) time_jar: Arc<TimeJar>,
+
+    pub(crate) proxy_rules: Option<Arc<ProxyRules>>,
+
+    #[cfg(unix)]
+    pub(crate) unix_client: RwLock<HyperClient<UnixConnector>>,
+
+    /// Unix socket requests are sent over unless given per request.
+    pub(crate) socket_path: Option<String>,
+
+    /// Applied to Unix socket requests, which do not go through reqwest.
+    pub(crate) request_timeout: Option<Duration>,
+
+    /// Redirects followed by Unix socket requests, reqwest applies its own policy.
+    pub(crate) redirect_limit: usize,
+
+    pub(crate) lifecycle: Arc<Lifecycle>,
+
+    /// Hard deadline after which a request fails, whatever it is waiting on.
+    pub(crate) watchdog: Option<Duration>,
+
+    pub(crate) limiter: Arc<Limiter>,
+
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+}
+
+/// Request failure, passed to the JS callback as an error with an optional `code`.
+#[derive(Debug)]
+pub enum RequestError {
+    Http(Error),
+    /// The certificate of a pinned host matched none of its pins.
+    #[cfg(feature = "rustls")]
+    Pin(PinError),
+    /// A redirect led to a host without a PAC result, with the redirect target.
+    PacUnresolved(Url),
+    Unix(String),
+    Closed,
+    /// The request task panicked, with the panic message.
+    Internal(String),
+    /// The request outlived the watchdog timeout.
+    Watchdog(Duration),
+}
+
+impl RequestError {
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            RequestError::Http(_) | RequestError::Unix(_) => None,
+            RequestError::Closed => Some("ERR_CLIENT_CLOSED"),
+            RequestError::Internal(_) => Some("ERR_INTERNAL"),
+            RequestError::Watchdog(_) => Some("ERR_WATCHDOG_TIMEOUT"),
+            RequestError::PacUnresolved(_) => Some("ERR_PAC_UNRESOLVED"),
+            #[cfg(feature = "rustls")]
+            RequestError::Pin(_) => Some("ERR_CERT_PIN_MISMATCH"),
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::Http(e) => e.fmt(f),
+            #[cfg(feature = "rustls")]
+            RequestError::Pin(e) => e.fmt(f),
+            RequestError::PacUnresolved(url) => write!(f, "No PAC result for redirect to {}", url),
+            RequestError::Unix(e) => e.fmt(f),
+            RequestError::Closed => write!(f, "Client closed before the request completed"),
+            RequestError::Internal(e) => write!(f, "Internal error: {}", e),
+            RequestError::Watchdog(timeout) => {
+                write!(f, "Request did not settle within the watchdog timeout of {:?}", timeout)
+            }
+        }
+    }
+}
+
+impl From<Error> for RequestError {
+    fn from(e: Error) -> Self {
+        #[cfg(feature = "rustls")]
+        if let Some(pin) = PinError::find(&e) {
+            return RequestError::Pin(pin);
+        }
+
+        if let Some(unresolved) = PacUnresolved::find(&e) {
+            return RequestError::PacUnresolved(unresolved.url.clone());
+        }
+
+        RequestError::Http(e)
+    }
 }
 
 #[derive(Debug)]
@@ -71,9 +200,34 @@ pub struct CallbackPayload {
     data: DataType,
 
     new_cookies: Vec<NewCookies>,
+
+    /// Proxy the last hop was sent through, `Some(None)` for direct connections,
+    /// `None` if the proxy is not resolved by the client.
+    proxy: Option<Option<String>>,
+    timings: Timings,
+}
+
+/// Durations of a request, reported in milliseconds.
+#[derive(Debug, Default)]
+pub struct Timings {
+    /// Waiting for a concurrency slot.
+    queued: Duration,
+
+    /// From the request call to the body being read, queueing included.
+    total: Duration,
 }
 
-impl Finalize for Client {}
+impl Finalize for Client {
+    /// Lets requests in flight complete before a runtime of the client's own is stopped.
+    fn finalize<'a, C: Context<'a>>(self, _cx: &mut C) {
+        self.lifecycle.close();
+
+        let lifecycle = self.lifecycle.clone();
+
+        self.runtime
+            .shutdown_after(async move { lifecycle.drained().await }, || {});
+    }
+}
 
 impl Client {
     #[inline]
@@ -151,14 +305,29 @@ impl Client {
         }
     }
 
+    /// Message of a panicked request task.
+    fn panic_message(e: JoinError) -> String {
+        match e.try_into_panic() {
+            Ok(panic) => match panic.downcast::<String>() {
+                Ok(v) => *v,
+                Err(panic) => match panic.downcast::<&'static str>() {
+                    Ok(v) => v.to_string(),
+                    Err(_) => "request task panicked".to_string(),
+                },
+            },
+            Err(e) => e.to_string(),
+        }
+    }
+
     /// Maps a response to inner data payload, essentially a copy and transform.
     /// Due to non-Send nature of FunctionContext, and non async of queue send fn prototype.
     #[inline]
     pub async fn map_response(
-        res: Result<Response, reqwest::Error>,
+        res: Result<Response, RequestError>,
         response_type: ResponseType,
         new_cookies: Vec<NewCookies>,
-    ) -> Result<CallbackPayload, reqwest::Error> {
+        proxy: Option<Option<String>>,
+    ) -> Result<CallbackPayload, RequestError> {
         match res {
             Ok(res) => {
                 let status = res.status().as_u16() as f64;
@@ -198,6 +367,8 @@ impl Client {
                     content_length,
                     data,
                     new_cookies,
+                    proxy,
+                    timings: Timings::default(),
                 })
             }
 
@@ -295,9 +466,508 @@ impl Client {
         obj.set(cx, "headers", headers)?;
         obj.set(cx, "newCookies", new_cookies)?;
 
+        let timings = JsObject::new(cx);
+
+        let queued = cx.number(payload.timings.queued.as_secs_f64() * 1000.0);
+        timings.set(cx, "queued", queued)?;
+
+        let total = cx.number(payload.timings.total.as_secs_f64() * 1000.0);
+        timings.set(cx, "total", total)?;
+
+        obj.set(cx, "timings", timings)?;
+
+        match payload.proxy {
+            Some(Some(proxy)) => {
+                let val = cx.string(proxy);
+
+                obj.set(cx, "proxy", val)?;
+            }
+            Some(None) => {
+                let val = cx.null();
+
+                obj.set(cx, "proxy", val)?;
+            }
+            None => {}
+        }
+
         Ok(obj)
     }
 
+    /// Converts a request to be sent over the Unix socket at `socket_path`, keeping its Host.
+    ///
+    /// Bodies are always buffered by `js_request`.
+    #[cfg(unix)]
+    fn unix_request(
+        socket_path: &str,
+        req: Request,
+    ) -> Result<hyper::Request<hyper::Body>, String> {
+        let url = req.url();
+
+        let path_and_query = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned(),
+        };
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => "localhost".to_owned(),
+        };
+
+        let uri = unix::socket_uri(socket_path, &path_and_query).map_err(|e| e.to_string())?;
+
+        let body = req
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|body| body.to_vec())
+            .unwrap_or_default();
+
+        let mut unix_req = hyper::Request::builder()
+            .method(req.method().clone())
+            .uri(uri)
+            .body(hyper::Body::from(body))
+            .map_err(|e| e.to_string())?;
+
+        *unix_req.headers_mut() = req.headers().clone();
+
+        if !unix_req.headers().contains_key(HOST) {
+            let host = HeaderValue::from_str(&host).map_err(|e| e.to_string())?;
+
+            unix_req.headers_mut().insert(HOST, host);
+        }
+
+        Ok(unix_req)
+    }
+
+    /// Sends a request over a Unix socket, retrying connection errors like `Attempter`.
+    ///
+    /// Follows reqwest in recording cookies and decompressing gzip and Brotli bodies. Redirects
+    /// within the origin are followed over the socket up to the client's redirect limit, others
+    /// are returned as is, as the socket only serves its own origin.
+    #[cfg(unix)]
+    async fn send_unix(
+        client: HyperClient<UnixConnector>,
+        socket_path: String,
+        mut req: Request,
+        attempts: usize,
+        timeout: Option<Duration>,
+        redirect_limit: usize,
+        time_jar: &TimeJar,
+    ) -> Result<Response, RequestError> {
+        if !req.headers().contains_key(ACCEPT_ENCODING) && !req.headers().contains_key(RANGE) {
+            req.headers_mut().insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip, br"));
+        }
+
+        // Like reqwest, the jar fills in cookies unless the request has its own.
+        if !req.headers().contains_key(COOKIE) {
+            Self::unix_cookies(&mut req, time_jar);
+        }
+
+        let send = async {
+            let mut redirects = 0;
+
+            loop {
+                let res = Self::send_unix_attempts(&client, &socket_path, &req, attempts).await?;
+
+                let (mut parts, body) = res.into_parts();
+
+                let body = hyper::body::to_bytes(body).await.map_err(|e| {
+                    RequestError::Unix(format!("Unix socket request to {} failed: {}", &socket_path, e))
+                })?;
+
+                let mut cookies = parts.headers.get_all(SET_COOKIE).iter().peekable();
+
+                if cookies.peek().is_some() {
+                    time_jar.set_cookies(&mut cookies, req.url());
+                }
+
+                let location = parts
+                    .headers
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| req.url().join(location).ok())
+                    .filter(|location| location.origin() == req.url().origin());
+
+                let redirect = redirect_limit > 0 && location.is_some();
+
+                match location {
+                    Some(location) if redirect && Self::unix_redirect(&mut req, parts.status) => {
+                        redirects += 1;
+
+                        if redirects > redirect_limit {
+                            return Err(RequestError::Unix(format!(
+                                "Unix socket request to {} failed: too many redirects",
+                                &socket_path
+                            )));
+                        }
+
+                        debug!("Redirect over Unix socket to {}", &location);
+
+                        *req.url_mut() = location;
+
+                        Self::unix_cookies(&mut req, time_jar);
+                    }
+                    _ => {
+                        let body = Self::unix_decode(&mut parts.headers, body).map_err(|e| {
+                            RequestError::Unix(format!(
+                                "Could not decode the response from Unix socket {}: {}",
+                                &socket_path, e
+                            ))
+                        })?;
+
+                        return Ok(Response::from(hyper::Response::from_parts(parts, body)));
+                    }
+                }
+            }
+        };
+
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, send).await.unwrap_or_else(|_| {
+                Err(RequestError::Unix(format!(
+                    "Unix socket request to {} timed out",
+                    &socket_path
+                )))
+            }),
+            None => send.await,
+        }
+    }
+
+    /// Sends a single hop over a Unix socket, retrying connection errors.
+    #[cfg(unix)]
+    async fn send_unix_attempts(
+        client: &HyperClient<UnixConnector>,
+        socket_path: &str,
+        req: &Request,
+        attempts: usize,
+    ) -> Result<hyper::Response<hyper::Body>, RequestError> {
+        let mut attempt = 0;
+
+        loop {
+            let unix_req = Self::unix_request(socket_path, req.try_clone().unwrap())
+                .map_err(RequestError::Unix)?;
+
+            match client.request(unix_req).await {
+                Err(e) if e.is_connect() && attempt < attempts => {
+                    attempt += 1;
+
+                    debug!("Unix socket connection error, retrying: {}", e);
+
+                    tokio::time::sleep(RETRY_DURATION * attempt as u32).await;
+                }
+                Err(e) => {
+                    return Err(RequestError::Unix(format!(
+                        "Unix socket request to {} failed: {}",
+                        socket_path, e
+                    )))
+                }
+                Ok(res) => return Ok(res),
+            }
+        }
+    }
+
+    /// Prepares the request for a redirect like reqwest does, returns whether to follow it.
+    ///
+    /// 301, 302 and 303 drop the body and switch to GET, 307 and 308 repeat the request as is.
+    #[cfg(unix)]
+    fn unix_redirect(req: &mut Request, status: StatusCode) -> bool {
+        match status {
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+                *req.body_mut() = None;
+
+                for header in &[TRANSFER_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, CONTENT_LENGTH] {
+                    req.headers_mut().remove(header);
+                }
+
+                if req.method() != Method::GET && req.method() != Method::HEAD {
+                    *req.method_mut() = Method::GET;
+                }
+
+                true
+            }
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => true,
+            _ => false,
+        }
+    }
+
+    /// Sets the cookies of the jar for the request URL, as reqwest does on every hop.
+    #[cfg(unix)]
+    fn unix_cookies(req: &mut Request, time_jar: &TimeJar) {
+        if let Some(cookie) = time_jar.cookies(req.url()) {
+            req.headers_mut().insert(COOKIE, cookie);
+        }
+    }
+
+    /// Decompresses a gzip or Brotli body, dropping the headers that described the encoded one.
+    #[cfg(unix)]
+    fn unix_decode(headers: &mut HeaderMap, body: Bytes) -> std::io::Result<Bytes> {
+        let encoding = headers
+            .get(CONTENT_ENCODING)
+            .and_then(|encoding| encoding.to_str().ok())
+            .map(|encoding| encoding.trim().to_ascii_lowercase());
+
+        let mut decoded = Vec::new();
+
+        match encoding.as_deref() {
+            _ if body.is_empty() => return Ok(body),
+            Some("gzip") => flate2::read::MultiGzDecoder::new(&body[..]).read_to_end(&mut decoded)?,
+            Some("br") => brotli::Decompressor::new(&body[..], 4096).read_to_end(&mut decoded)?,
+            _ => return Ok(body),
+        };
+
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(CONTENT_LENGTH);
+
+        Ok(Bytes::from(decoded))
+    }
+
+    /// Requests in flight and queued, in total and per origin.
+    pub fn js_stats(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        let (total, origins) = this.limiter.counts();
+
+        let obj = Self::counts_object(&mut cx, total)?;
+
+        let per_origin = JsObject::new(&mut cx);
+
+        for (origin, counts) in origins {
+            let val = Self::counts_object(&mut cx, counts)?;
+
+            per_origin.set(&mut cx, origin.as_str(), val)?;
+        }
+
+        obj.set(&mut cx, "origins", per_origin)?;
+
+        Ok(obj)
+    }
+
+    fn counts_object<'c>(cx: &mut FunctionContext<'c>, counts: Counts) -> JsResult<'c, JsObject> {
+        let obj = JsObject::new(cx);
+
+        let in_flight = cx.number(counts.in_flight as f64);
+        obj.set(cx, "inFlight", in_flight)?;
+
+        let queued = cx.number(counts.queued as f64);
+        obj.set(cx, "queued", queued)?;
+
+        Ok(obj)
+    }
+
+    /// Records the proxy a PAC script chose for a host, an empty proxy meaning DIRECT.
+    pub fn js_set_pac_proxy(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let host = cx.argument::<JsString>(0)?.value(&mut cx);
+        let proxy = cx.argument::<JsString>(1)?.value(&mut cx);
+
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        let mut proxy = match proxy.as_str() {
+            "" => None,
+            _ => match ProxyRules::parse_proxy(&proxy, None) {
+                Ok(v) => Some(v),
+                Err(e) => cx.throw_error(e)?,
+            },
+        };
+
+        match this.proxy_rules.as_deref() {
+            Some(ProxyRules { pac: Some(pac), socks_remote_dns, .. }) => {
+                if let Some(proxy) = proxy.as_mut().filter(|_| *socks_remote_dns) {
+                    ProxyRules::remote_dns(proxy);
+                }
+
+                debug!("PAC proxy for {}: {:?}", &host, proxy.as_ref().map(ProxyRules::redact));
+
+                pac.write().unwrap().insert(host, proxy);
+            }
+            _ => {
+                cx.throw_error("Client is not configured with a PAC script")?;
+            }
+        }
+
+        Ok(cx.undefined())
+    }
+
+    /// Connection pools per origin, with the protocol of their last response, requests in
+    /// flight and the time since the last one completed.
+    ///
+    /// hyper does not expose its pooled connections, so they are not counted.
+    pub fn js_connections(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        let obj = JsObject::new(&mut cx);
+
+        for pool in this.pools.list() {
+            let entry = JsObject::new(&mut cx);
+
+            let http_version: Handle<JsValue> = match pool.version {
+                Some(version) => cx.string(format!("{:?}", version)).upcast(),
+                None => cx.null().upcast(),
+            };
+            entry.set(&mut cx, "httpVersion", http_version)?;
+
+            let in_flight = cx.number(pool.in_flight as f64);
+            entry.set(&mut cx, "inFlight", in_flight)?;
+
+            let idle_time: Handle<JsValue> = match pool.idle {
+                Some(idle) => cx.number(idle.as_secs_f64()).upcast(),
+                None => cx.null().upcast(),
+            };
+            entry.set(&mut cx, "idleTime", idle_time)?;
+
+            obj.set(&mut cx, pool.origin.as_str(), entry)?;
+        }
+
+        Ok(obj)
+    }
+
+    /// Closes the connection pool of the origin of a URL, returning whether it had one.
+    pub fn js_close_idle_connections(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+        let url = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        let origin = match Url::parse(&url) {
+            Ok(url) => url.origin().ascii_serialization(),
+            Err(e) => cx.throw_error(format!("Invalid URL {}: {}", url, e))?,
+        };
+
+        let closed = this.pools.close(&origin);
+
+        debug!("Closed the connection pool of {}: {}", &origin, closed);
+
+        Ok(cx.boolean(closed))
+    }
+
+    /// Replaces the connection pools with empty ones, keeping the runtime, cookies, resolvers
+    /// and proxy state.
+    ///
+    /// The reqwest clients are built again from their recorded steps, as reqwest clients cannot
+    /// be cloned without sharing their pool. Idle connections close once dropped, connections in
+    /// use once their requests complete.
+    pub fn js_reset_connections(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        if let Err(e) = this.pools.reset() {
+            cx.throw_error(format!("Invalid client configuration: {}", e))?;
+        }
+
+        #[cfg(unix)]
+        {
+            *this.unix_client.write().unwrap() = HyperClient::builder().build(UnixConnector);
+        }
+
+        debug!("Connection pool reset");
+
+        Ok(cx.undefined())
+    }
+
+    /// JS error for a request failure, with its `code` and the `url` of unresolved PAC redirects.
+    fn error_value<'a, C: Context<'a>>(cx: &mut C, e: &RequestError) -> JsResult<'a, JsError> {
+        let err = cx.error(e.to_string())?;
+
+        if let Some(code) = e.code() {
+            let code = cx.string(code);
+
+            err.set(cx, "code", code)?;
+        }
+
+        if let RequestError::PacUnresolved(url) = e {
+            let url = cx.string(url.as_str());
+
+            err.set(cx, "url", url)?;
+        }
+
+        Ok(err)
+    }
+
+    /// Opens a connection to an origin and parks it in the pool with a `HEAD /` request.
+    ///
+    /// reqwest cannot connect without a request, so one is sent, but it follows no redirects,
+    /// stores no cookies and bypasses the concurrency and rate limits. Calls back with the
+    /// negotiated HTTP version and the time taken.
+    pub fn js_preconnect(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let origin = cx.argument::<JsString>(0)?.value(&mut cx);
+        let callback = cx.argument::<JsFunction>(1)?.root(&mut cx);
+
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        if this.socket_path.is_some() {
+            cx.throw_error("Clients with a socketPath cannot preconnect")?;
+        }
+
+        let mut url = match Url::parse(&origin) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid origin {}: {}", origin, e))?,
+        };
+
+        url.set_path("/");
+        url.set_query(None);
+        url.set_fragment(None);
+
+        let in_flight = match this.lifecycle.start() {
+            Some(v) => v,
+            None => {
+                let err = cx.error("Client is closed")?;
+                let code = cx.string(RequestError::Closed.code().unwrap());
+
+                err.set(&mut cx, "code", code)?;
+
+                cx.throw(err)?
+            }
+        };
+
+        debug!("Preconnecting to {}", &origin);
+
+        let checkout = match this.pools.checkout(Some(&url)) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid client configuration: {}", e))?,
+        };
+
+        let queue = cx.channel();
+
+        let lifecycle = this.lifecycle.clone();
+
+        this.runtime.spawn(async move {
+            let start = Instant::now();
+
+            let send = PRECONNECT.scope((), checkout.client().head(url).send());
+
+            let res = tokio::select! {
+                res = send => res.map(|res| res.version()).map_err(RequestError::from),
+                _ = lifecycle.aborted() => Err(RequestError::Closed),
+            };
+
+            let duration = start.elapsed();
+
+            queue.send(move |mut cx| {
+                let cb = callback.into_inner(&mut cx);
+                let this = cx.undefined();
+
+                let args: Vec<Handle<JsValue>> = match res {
+                    Ok(version) => {
+                        let ret = JsObject::new(&mut cx);
+
+                        let http_version = cx.string(format!("{:?}", version));
+                        ret.set(&mut cx, "httpVersion", http_version)?;
+
+                        let duration = cx.number(duration.as_secs_f64() * 1000.0);
+                        ret.set(&mut cx, "duration", duration)?;
+
+                        vec![cx.null().upcast(), ret.upcast()]
+                    }
+                    Err(e) => vec![Self::error_value(&mut cx, &e)?.upcast()],
+                };
+
+                cb.call(&mut cx, this, args)?;
+
+                Ok(())
+            });
+
+            drop(in_flight);
+        });
+
+        Ok(cx.undefined())
+    }
+
     pub fn js_request(mut cx: FunctionContext) -> JsResult<JsUndefined> {
         let url = cx.argument::<JsString>(0)?.value(&mut cx);
         let args = cx.argument::<JsObject>(1)?;
@@ -305,6 +975,18 @@ impl Client {
 
         let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
 
+        let in_flight = match this.lifecycle.start() {
+            Some(v) => v,
+            None => {
+                let err = cx.error("Client is closed")?;
+                let code = cx.string(RequestError::Closed.code().unwrap());
+
+                err.set(&mut cx, "code", code)?;
+
+                cx.throw(err)?
+            }
+        };
+
         let keys = Self::object_keys(&mut cx, &args)?;
 
         let method = Method::from_str(&args.get::<JsString, _, _>(&mut cx, "method")?.value(&mut cx)).unwrap();
@@ -316,7 +998,39 @@ impl Client {
             &method, &url, &attempts
         );
 
-        let mut builder = this.client.request(method.clone(), url);
+        let request_url = Url::parse(&url).ok();
+
+        let origin = request_url
+            .as_ref()
+            .map_or_else(|| url.clone(), |url| url.origin().ascii_serialization());
+
+        let priority = if keys.contains_key("priority") {
+            args.get::<JsNumber, _, _>(&mut cx, "priority")?.value(&mut cx) as i32
+        } else {
+            0
+        };
+
+        // Pools log the proxy of each attempt.
+        if let (Some(rules), Some(request_url)) = (&this.proxy_rules, &request_url) {
+            if rules.pool.is_none() {
+                match rules.resolve(request_url) {
+                    Some(proxy) => debug!("Request to {} via proxy {}", &url, ProxyRules::redact(&proxy)),
+                    None => debug!("Request to {} without proxy", &url),
+                }
+            }
+        }
+
+        // Unix socket requests open no TCP connections, so they do not need a pool.
+        let unix_request = keys.contains_key("socketPath") || this.socket_path.is_some();
+
+        let pooled_url = request_url.as_ref().filter(|_| !unix_request);
+
+        let checkout = match this.pools.checkout(pooled_url) {
+            Ok(v) => v,
+            Err(e) => cx.throw_error(format!("Invalid client configuration: {}", e))?,
+        };
+
+        let mut builder = checkout.client().request(method.clone(), url);
 
         if keys.contains_key("headers") {
             let headers: Handle<JsObject> = args.get(&mut cx, "headers")?;
@@ -360,32 +1074,176 @@ impl Client {
 
         let response_type = ResponseType::from_str(&args.get::<JsString, _, _>(&mut cx, "responseType")?.value(&mut cx)).unwrap();
 
+        let socket_path = if keys.contains_key("socketPath") {
+            Some(args.get::<JsString, _, _>(&mut cx, "socketPath")?.value(&mut cx))
+        } else {
+            this.socket_path.clone()
+        };
+
+        #[cfg(not(unix))]
+        if socket_path.is_some() {
+            cx.throw_error("Unix sockets are not supported on this platform")?;
+        }
+
+        #[cfg(unix)]
+        let unix = match socket_path {
+            Some(socket_path) => match builder.try_clone().unwrap().build() {
+                Ok(req) => {
+                    debug!("Request over Unix socket {}", &socket_path);
+
+                    let client = this.unix_client.read().unwrap().clone();
+
+                    Some((client, socket_path, req, this.request_timeout, this.redirect_limit))
+                }
+                Err(e) => cx.throw_error(format!("Invalid request: {}", e))?,
+            },
+            None => None,
+        };
+
         debug!("Request response type: {:?}", &response_type);
 
         let queue = cx.channel();
 
         let time_jar = this.time_jar.clone();
 
+        let proxy_rules = this.proxy_rules.clone();
+
+        let pool_rules = this.proxy_rules.clone().filter(|rules| rules.pool.is_some());
+
+        let lifecycle = this.lifecycle.clone();
+
+        let watchdog = this.watchdog;
+
+        let limiter = this.limiter.clone();
+
+        let rate_limiter = this.rate_limiter.clone();
+
+        // Unix socket requests are not rate limited, having no origin on the network.
+        let rate_limited_url = request_url.clone().filter(|_| !unix_request);
+
         this.runtime.spawn(async move {
             let request_time = Instant::now();
 
-            let res = FutureRetry::new(
-                || builder.try_clone().unwrap().send(),
-                Attempter::new(method, attempts),
-            )
-            .await
-            .map_err(|(e, attempts)| {
-                debug!("Request error after {} attempts: {}", attempts, e);
-                e
-            })
-            .map(|(r, attempts)| {
-                debug!("Request successful after {} attempts", attempts);
-                r
+            // Run as a task of its own, so a panic fails this request instead of leaving it unsettled.
+            let mut work = tokio::spawn(async move {
+                // The first token is taken before a concurrency slot, so a request waiting for its
+                // token does not hold a slot other origins could use.
+                if let Some(url) = &rate_limited_url {
+                    rate_limiter.acquire(url).await;
+                }
+
+                let permit = limiter.acquire(&origin, priority).await;
+
+                let queued = request_time.elapsed();
+
+                let served = Mutex::new(None);
+
+                let res: Result<Response, RequestError> = async {
+                    #[cfg(unix)]
+                    if let Some((client, socket_path, req, timeout, redirect_limit)) = unix {
+                        *served.lock().unwrap() = Some(None);
+
+                        return Self::send_unix(client, socket_path, req, attempts, timeout, redirect_limit, &time_jar)
+                            .await;
+                    }
+
+                    let prepaid = AtomicBool::new(true);
+
+                    let res = FutureRetry::new(
+                        || {
+                            let send = || builder.try_clone().unwrap().send();
+
+                            let pool = pool_rules.as_ref().and_then(|rules| rules.pool.as_ref());
+                            let served = &served;
+                            let url = &request_url;
+                            let rate_limited_url = &rate_limited_url;
+                            let rate_limiter = &rate_limiter;
+                            let prepaid = &prepaid;
+
+                            async move {
+                                // Every attempt takes a token, retries included.
+                                if let (Some(url), false) = (rate_limited_url, prepaid.swap(false, Ordering::SeqCst)) {
+                                    rate_limiter.acquire(url).await;
+                                }
+
+                                match (pool, url) {
+                                    (Some(pool), Some(url)) => {
+                                        let (proxy, res) = pool.send(url, send).await;
+
+                                        // Sticky pools pick per hop, resolved again below.
+                                        if pool.rotation() != Rotation::Sticky {
+                                            *served.lock().unwrap() = Some(Some(proxy));
+                                        }
+
+                                        res
+                                    }
+                                    _ => send().await,
+                                }
+                            }
+                        },
+                        Attempter::new(method, attempts),
+                    )
+                    .await
+                    .map_err(|(e, attempts)| {
+                        debug!("Request error after {} attempts: {}", attempts, e);
+                        e
+                    })
+                    .map(|(r, attempts)| {
+                        debug!("Request successful after {} attempts", attempts);
+                        r
+                    })?;
+
+                    Ok(res)
+                }
+                .await;
+
+                if let Ok(res) = &res {
+                    checkout.record(res.url(), res.version());
+                }
+
+                let new_cookies = time_jar.cookies_since(request_time);
+
+                // Reported for the last hop, as redirects may lead to hosts with other proxies.
+                let proxy = match (served.into_inner().unwrap(), &res, &proxy_rules) {
+                    (Some(served), _, _) => Some(served.as_ref().map(ProxyRules::redact)),
+                    (None, Ok(res), Some(rules)) => {
+                        Some(rules.resolve(res.url()).as_ref().map(ProxyRules::redact))
+                    }
+                    _ => None,
+                };
+
+                let res = Self::map_response(res, response_type, new_cookies, proxy).await;
+
+                drop(permit);
+
+                res.map(|mut payload| {
+                    payload.timings = Timings {
+                        queued,
+                        total: request_time.elapsed(),
+                    };
+
+                    payload
+                })
             });
 
-            let new_cookies = time_jar.cookies_since(request_time);
+            let deadline = async {
+                match watchdog {
+                    Some(v) => tokio::time::sleep(v).await,
+                    None => future::pending::<()>().await,
+                }
+            };
+
+            let res = tokio::select! {
+                res = &mut work => res.unwrap_or_else(|e| {
+                    error!("Request task failed: {}", e);
+
+                    Err(RequestError::Internal(Self::panic_message(e)))
+                }),
+                _ = lifecycle.aborted() => Err(RequestError::Closed),
+                _ = deadline => Err(RequestError::Watchdog(watchdog.unwrap_or_default())),
+            };
 
-            let res = Self::map_response(res, response_type, new_cookies).await;
+            work.abort();
 
             queue.send(|mut cx| {
                 let cb = callback.into_inner(&mut cx);
@@ -402,7 +1260,9 @@ impl Client {
                         cb.call(&mut cx, this, args)?;
                     }
                     Err(e) => {
-                        let args: Vec<Handle<JsValue>> = vec![cx.error(e.to_string())?.upcast()];
+                        let err = Self::error_value(&mut cx, &e)?;
+
+                        let args: Vec<Handle<JsValue>> = vec![err.upcast()];
 
                         debug!("Called back with error");
 
@@ -412,6 +1272,66 @@ impl Client {
 
                 Ok(())
             });
+
+            drop(in_flight);
+        });
+
+        Ok(cx.undefined())
+    }
+
+    /// Stops accepting requests, then drains or aborts those in flight and stops the runtime.
+    ///
+    /// Requests still in flight after `timeout` seconds are aborted, a `null` timeout waits for
+    /// them all. Calls back once the runtime is stopped.
+    pub fn js_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let abort = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+        let timeout = cx.argument::<JsValue>(1)?;
+        let callback = cx.argument::<JsFunction>(2)?.root(&mut cx);
+
+        let timeout = if timeout.is_a::<JsNull, _>(&mut cx) {
+            None
+        } else {
+            let seconds = timeout.downcast_or_throw::<JsNumber, _>(&mut cx)?.value(&mut cx);
+
+            match Duration::try_from_secs_f64(seconds) {
+                Ok(v) => Some(v),
+                Err(_) => cx.throw_error(format!("Invalid close timeout: {}", seconds))?,
+            }
+        };
+
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        this.lifecycle.close();
+
+        let lifecycle = this.lifecycle.clone();
+
+        let queue = cx.channel();
+
+        let until = async move {
+            if abort {
+                lifecycle.abort();
+            } else if let Some(timeout) = timeout {
+                if tokio::time::timeout(timeout, lifecycle.drained()).await.is_err() {
+                    debug!("Aborting requests still in flight after {:?}", timeout);
+
+                    lifecycle.abort();
+                }
+            }
+
+            lifecycle.drained().await;
+        };
+
+        this.runtime.shutdown_after(until, move || {
+            queue.send(|mut cx| {
+                let cb = callback.into_inner(&mut cx);
+                let this = cx.undefined();
+
+                debug!("Client closed");
+
+                cb.call(&mut cx, this, Vec::<Handle<JsValue>>::new())?;
+
+                Ok(())
+            });
         });
 
         Ok(cx.undefined())
@@ -447,6 +1367,11 @@ impl ErrorHandler<Error> for Attempter {
             return RetryPolicy::ForwardError(e);
         }
 
+        // Retrying cannot help until the PAC script is evaluated for the redirect target.
+        if PacUnresolved::find(&e).is_some() {
+            return RetryPolicy::ForwardError(e);
+        }
+
         // Check if the error is io::ErrorKind::BrokenPipe
         let mut source = e.source();
         let mut is_broken_pipe = false;
@@ -504,3 +1429,35 @@ impl ErrorHandler<Error> for Attempter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_panics_of_request_tasks() {
+        let e = tokio::spawn(async { panic!("formatted {}", 1) }).await.unwrap_err();
+
+        assert_eq!(Client::panic_message(e), "formatted 1");
+
+        let e = tokio::spawn(async { panic!("static") }).await.unwrap_err();
+
+        assert_eq!(Client::panic_message(e), "static");
+
+        let e = tokio::spawn(async { std::panic::panic_any(1) }).await.unwrap_err();
+
+        assert_eq!(Client::panic_message(e), "request task panicked");
+    }
+
+    #[tokio::test]
+    async fn fails_requests_whose_task_panicked() {
+        let res: Result<(), RequestError> = tokio::spawn(async { panic!("request task") })
+            .await
+            .map_err(|e| RequestError::Internal(Client::panic_message(e)));
+
+        let e = res.unwrap_err();
+
+        assert_eq!(e.code(), Some("ERR_INTERNAL"));
+        assert_eq!(e.to_string(), "Internal error: request task");
+    }
+}
@@ -0,0 +1,491 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::error::Error as StdError;
+use std::future::{self, Future};
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use neon::prelude::*;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client as ReqwestClient, Url};
+
+use tokio::net::lookup_host;
+use tokio::sync::oneshot;
+
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Addresses of a host, valid for `ttl`.
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    pub addrs: Vec<IpAddr>,
+
+    pub ttl: Duration,
+}
+
+pub type LookupFuture = Pin<Box<dyn Future<Output = Result<Lookup, BoxError>> + Send>>;
+
+/// A source of DNS answers, plugged into reqwest through `CachingResolver`.
+pub trait HostResolver: Send + Sync {
+    fn lookup(&self, host: &str) -> LookupFuture;
+}
+
+/// Caches the answers of a `HostResolver` for their TTL.
+///
+/// Answers with a zero TTL are not cached, expired answers are dropped when caching another.
+pub struct CachingResolver {
+    inner: Arc<dyn HostResolver>,
+
+    cache: Arc<Mutex<HashMap<String, (Vec<IpAddr>, Instant)>>>,
+}
+
+impl CachingResolver {
+    pub fn new(inner: Arc<dyn HostResolver>) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// hyper replaces the port with the one of the request URL.
+    fn socket_addrs(addrs: Vec<IpAddr>) -> Addrs {
+        Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0)))
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+
+        if let Some((addrs, expires)) = self.cache.lock().unwrap().get(&host) {
+            if *expires > Instant::now() {
+                debug!("Resolved {} from cache: {:?}", &host, addrs);
+
+                return Box::pin(future::ready(Ok(Self::socket_addrs(addrs.clone()))));
+            }
+        }
+
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+
+        Box::pin(async move {
+            let lookup = inner.lookup(&host).await?;
+
+            if lookup.addrs.is_empty() {
+                return Err(format!("No addresses found for {}", host).into());
+            }
+
+            debug!("Resolved {} to {:?}, ttl {:?}", &host, &lookup.addrs, lookup.ttl);
+
+            if !lookup.ttl.is_zero() {
+                let now = Instant::now();
+
+                let mut cache = cache.lock().unwrap();
+
+                cache.retain(|_, (_, expires)| *expires > now);
+                cache.insert(host, (lookup.addrs.clone(), now + lookup.ttl));
+            }
+
+            Ok(Self::socket_addrs(lookup.addrs))
+        })
+    }
+}
+
+/// Resolves hostnames through a JS function called with `(hostname, pending)`,
+/// which answers by calling `dnsLookupComplete` on `pending`.
+pub struct JsResolver {
+    channel: Channel,
+
+    lookup: Arc<Root<JsFunction>>,
+}
+
+impl JsResolver {
+    pub fn new(cx: &mut FunctionContext, lookup: Handle<JsFunction>) -> Self {
+        let mut channel = cx.channel();
+
+        // Lookups only happen for requests, which keep the event loop alive themselves.
+        channel.unref(cx);
+
+        Self {
+            channel,
+            lookup: Arc::new(lookup.root(cx)),
+        }
+    }
+}
+
+impl HostResolver for JsResolver {
+    fn lookup(&self, host: &str) -> LookupFuture {
+        let (tx, rx) = oneshot::channel();
+
+        let lookup = self.lookup.clone();
+        let host = host.to_owned();
+
+        self.channel.send(move |mut cx| {
+            let lookup = lookup.to_inner(&mut cx);
+            let this = cx.undefined();
+
+            let host = cx.string(host);
+            let pending = JsBox::new(&mut cx, PendingLookup(RefCell::new(Some(tx))));
+
+            let args: Vec<Handle<JsValue>> = vec![host.upcast(), pending.upcast()];
+
+            lookup.call(&mut cx, this, args)?;
+
+            Ok(())
+        });
+
+        Box::pin(async move {
+            match rx.await {
+                Ok(res) => res.map_err(BoxError::from),
+                Err(_) => Err("DNS resolver did not complete the lookup".into()),
+            }
+        })
+    }
+}
+
+/// A lookup waiting on its JS resolver.
+pub struct PendingLookup(RefCell<Option<oneshot::Sender<Result<Lookup, String>>>>);
+
+impl Finalize for PendingLookup {}
+
+impl PendingLookup {
+    /// Completes a lookup with `(error, records)`, records being `{ address, ttl }` objects.
+    ///
+    /// The lowest TTL of the records applies to all of them.
+    pub fn js_complete(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let this = cx.this().downcast_or_throw::<JsBox<Self>, _>(&mut cx)?;
+
+        let error = cx.argument::<JsValue>(0)?;
+
+        let res = match error.downcast::<JsString, _>(&mut cx) {
+            Ok(error) => Err(error.value(&mut cx)),
+            Err(_) => {
+                let records = cx.argument::<JsArray>(1)?.to_vec(&mut cx)?;
+
+                let mut addrs = Vec::with_capacity(records.len());
+                let mut ttl: Option<f64> = None;
+
+                for record in records {
+                    let record = record.downcast_or_throw::<JsObject, _>(&mut cx)?;
+
+                    let address = record.get::<JsString, _, _>(&mut cx, "address")?.value(&mut cx);
+
+                    match address.parse::<IpAddr>() {
+                        Ok(v) => addrs.push(v),
+                        Err(_) => cx.throw_error(format!("Invalid resolved address: {}", address))?,
+                    }
+
+                    let record_ttl = record
+                        .get_opt::<JsNumber, _, _>(&mut cx, "ttl")?
+                        .map_or(0.0, |v| v.value(&mut cx));
+
+                    ttl = Some(ttl.map_or(record_ttl, |v| v.min(record_ttl)));
+                }
+
+                Ok(Lookup {
+                    addrs,
+                    ttl: Duration::try_from_secs_f64(ttl.unwrap_or(0.0)).unwrap_or(Duration::ZERO),
+                })
+            }
+        };
+
+        if let Some(tx) = this.0.borrow_mut().take() {
+            tx.send(res).ok();
+        }
+
+        Ok(cx.undefined())
+    }
+}
+
+/// Address family connections are restricted to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    pub fn matches(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl FromStr for IpFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v4" => Ok(IpFamily::V4),
+            "v6" => Ok(IpFamily::V6),
+            _ => Err(format!("Invalid IP family: {}", s)),
+        }
+    }
+}
+
+/// Drops the addresses of the other family from the answers of a resolver.
+pub struct FamilyFilter {
+    inner: Arc<dyn HostResolver>,
+
+    family: IpFamily,
+}
+
+impl FamilyFilter {
+    pub fn new(inner: Arc<dyn HostResolver>, family: IpFamily) -> Self {
+        Self { inner, family }
+    }
+}
+
+impl HostResolver for FamilyFilter {
+    fn lookup(&self, host: &str) -> LookupFuture {
+        let lookup = self.inner.lookup(host);
+        let family = self.family;
+
+        Box::pin(async move {
+            let mut lookup = lookup.await?;
+
+            lookup.addrs.retain(|ip| family.matches(ip));
+
+            Ok(lookup)
+        })
+    }
+}
+
+/// The platform's resolver through `getaddrinfo`, which exposes no TTL.
+pub struct SystemResolver;
+
+impl HostResolver for SystemResolver {
+    fn lookup(&self, host: &str) -> LookupFuture {
+        let host = host.to_owned();
+
+        Box::pin(async move {
+            let addrs = lookup_host((host.as_str(), 0)).await?;
+
+            Ok(Lookup {
+                addrs: addrs.map(|addr| addr.ip()).collect(),
+                ttl: Duration::ZERO,
+            })
+        })
+    }
+}
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// DNS-over-HTTPS endpoint, reached without DNS through its bootstrap addresses.
+#[derive(Debug)]
+pub struct DohConfig {
+    pub url: Url,
+
+    pub bootstrap: Vec<IpAddr>,
+
+    /// Use the system resolver when the endpoint fails, but not for negative answers.
+    pub fallback: bool,
+
+    pub timeout: Duration,
+}
+
+/// Resolves hostnames with RFC 8484 POST queries for A and AAAA records.
+pub struct DohResolver {
+    client: ReqwestClient,
+
+    url: Url,
+
+    fallback: bool,
+}
+
+impl DohResolver {
+    pub fn new(client: ReqwestClient, url: Url, fallback: bool) -> Self {
+        Self {
+            client,
+            url,
+            fallback,
+        }
+    }
+
+    async fn query(
+        client: ReqwestClient,
+        url: Url,
+        host: String,
+        qtype: u16,
+    ) -> Result<Lookup, String> {
+        let res = client
+            .post(url)
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .header(ACCEPT, DNS_MESSAGE)
+            .body(encode_query(&host, qtype)?)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !res.status().is_success() {
+            return Err(format!("DoH server responded with {}", res.status()));
+        }
+
+        let body = res.bytes().await.map_err(|e| e.to_string())?;
+
+        decode_response(&body)
+    }
+
+    /// Queries A and AAAA records, a failed query counting as empty when the other one
+    /// returned addresses.
+    async fn resolve(client: ReqwestClient, url: Url, host: String) -> Result<Lookup, String> {
+        let aaaa = tokio::spawn(Self::query(client.clone(), url.clone(), host.clone(), TYPE_AAAA));
+
+        let v4 = Self::query(client, url, host.clone(), TYPE_A).await;
+        let v6 = aaaa.await.map_err(|e| e.to_string()).and_then(|res| res);
+
+        let lookups = match (v4, v6) {
+            (Ok(v4), Ok(v6)) => vec![v4, v6],
+            (Ok(lookup), Err(e)) | (Err(e), Ok(lookup)) if !lookup.addrs.is_empty() => {
+                debug!("DoH query of {} failed for one address family: {}", &host, e);
+
+                vec![lookup]
+            }
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        };
+
+        let ttl = lookups
+            .iter()
+            .filter(|lookup| !lookup.addrs.is_empty())
+            .map(|lookup| lookup.ttl)
+            .min()
+            .unwrap_or(Duration::ZERO);
+
+        Ok(Lookup {
+            addrs: lookups.into_iter().flat_map(|lookup| lookup.addrs).collect(),
+            ttl,
+        })
+    }
+}
+
+impl HostResolver for DohResolver {
+    fn lookup(&self, host: &str) -> LookupFuture {
+        let client = self.client.clone();
+        let url = self.url.clone();
+        let host = host.to_owned();
+        let fallback = self.fallback;
+
+        Box::pin(async move {
+            match Self::resolve(client, url, host.clone()).await {
+                Ok(lookup) => Ok(lookup),
+                Err(e) if fallback => {
+                    warn!("DoH lookup of {} failed, using the system resolver: {}", &host, e);
+
+                    SystemResolver.lookup(&host).await
+                }
+                Err(e) => Err(format!("DoH lookup of {} failed: {}", host, e).into()),
+            }
+        })
+    }
+}
+
+/// Encodes a recursive query (RFC 1035 4.1) with ID 0, as RFC 8484 recommends for caching.
+fn encode_query(host: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    let mut msg = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid hostname: {}", host));
+        }
+
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+
+    msg.push(0);
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&1u16.to_be_bytes());
+
+    Ok(msg)
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(msg.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(msg: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(msg.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Returns the offset following a possibly compressed name.
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        match *msg.get(pos)? {
+            0 => return Some(pos + 1),
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+/// Decodes the A and AAAA answers of a response, valid for their lowest TTL.
+///
+/// CNAME records are skipped, as resolvers answer with the records of their target as well.
+/// A name without addresses is a successful lookup without any.
+fn decode_response(msg: &[u8]) -> Result<Lookup, String> {
+    let flags = read_u16(msg, 2).ok_or("Malformed DNS response")?;
+
+    match flags & 0x000f {
+        0 | 3 => {}
+        rcode => return Err(format!("DNS query failed with rcode {}", rcode)),
+    }
+
+    let answers = (|| {
+        let questions = read_u16(msg, 4)?;
+        let answers = read_u16(msg, 6)?;
+
+        let mut pos = 12;
+
+        for _ in 0..questions {
+            pos = skip_name(msg, pos)? + 4;
+        }
+
+        let mut addrs = Vec::new();
+        let mut ttl = u32::MAX;
+
+        for _ in 0..answers {
+            pos = skip_name(msg, pos)?;
+
+            let rtype = read_u16(msg, pos)?;
+            let record_ttl = read_u32(msg, pos + 4)?;
+            let len = read_u16(msg, pos + 8)? as usize;
+            let data = msg.get(pos + 10..pos + 10 + len)?;
+
+            pos += 10 + len;
+
+            let addr = match rtype {
+                TYPE_A => IpAddr::from(<[u8; 4]>::try_from(data).ok()?),
+                TYPE_AAAA => IpAddr::from(<[u8; 16]>::try_from(data).ok()?),
+                _ => continue,
+            };
+
+            addrs.push(addr);
+            ttl = ttl.min(record_ttl);
+        }
+
+        Some((addrs, ttl))
+    })();
+
+    match answers {
+        Some((addrs, _)) if addrs.is_empty() => Ok(Lookup {
+            addrs,
+            ttl: Duration::ZERO,
+        }),
+        Some((addrs, ttl)) => Ok(Lookup {
+            addrs,
+            ttl: Duration::from_secs(ttl as u64),
+        }),
+        None => Err("Malformed DNS response".to_owned()),
+    }
+}
@@ -2,20 +2,66 @@ extern crate static_vcruntime;
 
 use neon::prelude::*;
 
+mod bridge;
 mod builder;
 mod client;
+mod dns;
+mod lifecycle;
+mod limiter;
+#[cfg(feature = "rustls")]
+mod pinning;
+mod pool;
+mod proxy;
+mod rate_limit;
+mod runtime;
 mod time_jar;
+#[cfg(feature = "rustls")]
+mod tls;
+#[cfg(unix)]
+mod unix;
 
 use builder::Builder;
 use client::Client;
+use dns::PendingLookup;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("Either the `native-tls` or the `rustls` feature must be enabled");
+
+#[cfg(all(feature = "native-tls", feature = "rustls"))]
+compile_error!("The `native-tls` and `rustls` features are exclusive, build rustls with `--no-default-features`");
+
+/// TLS backend this build was compiled with.
+#[cfg(feature = "native-tls")]
+const TLS_BACKEND: &str = "native-tls";
+
+#[cfg(feature = "rustls")]
+const TLS_BACKEND: &str = "rustls";
+
+fn tls_backend(mut cx: FunctionContext) -> JsResult<JsString> {
+    Ok(cx.string(TLS_BACKEND))
+}
 
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
+    cx.export_function("tlsBackend", tls_backend)?;
+
     cx.export_function("clientRequest", Client::js_request)?;
+    cx.export_function("clientSetPacProxy", Client::js_set_pac_proxy)?;
+    cx.export_function("clientPreconnect", Client::js_preconnect)?;
+    cx.export_function("clientResetConnections", Client::js_reset_connections)?;
+    cx.export_function("clientConnections", Client::js_connections)?;
+    cx.export_function("clientCloseIdleConnections", Client::js_close_idle_connections)?;
+    cx.export_function("clientClose", Client::js_close)?;
+    cx.export_function("clientStats", Client::js_stats)?;
+
+    cx.export_function("dnsLookupComplete", PendingLookup::js_complete)?;
 
     cx.export_function("builderNew", Builder::js_new)?;
     cx.export_function("builderConnectTimeout", Builder::js_connect_timeout)?;
     cx.export_function("builderRequestTimeout", Builder::js_request_timeout)?;
+    cx.export_function("builderWatchdogTimeout", Builder::js_watchdog_timeout)?;
+    cx.export_function("builderConcurrencyLimits", Builder::js_concurrency_limits)?;
+    cx.export_function("builderRateLimit", Builder::js_rate_limit)?;
     cx.export_function("builderRedirectLimit", Builder::js_redirect_limit)?;
     cx.export_function("builderHttpsOnly", Builder::js_https_only)?;
     cx.export_function("builderStripSensitiveHeaders", Builder::js_strip_sensitive_headers)?;
@@ -23,7 +69,59 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
         "builderHttps2AdaptiveWindow",
         Builder::js_http2_adaptive_window,
     )?;
+    cx.export_function(
+        "builderHttp2InitialStreamWindowSize",
+        Builder::js_http2_initial_stream_window_size,
+    )?;
+    cx.export_function(
+        "builderHttp2InitialConnectionWindowSize",
+        Builder::js_http2_initial_connection_window_size,
+    )?;
+    cx.export_function("builderHttp2MaxFrameSize", Builder::js_http2_max_frame_size)?;
+    cx.export_function("builderPoolMaxIdlePerHost", Builder::js_pool_max_idle_per_host)?;
+    cx.export_function("builderPoolIdleTimeout", Builder::js_pool_idle_timeout)?;
+    cx.export_function("builderTcpKeepalive", Builder::js_tcp_keepalive)?;
+    cx.export_function("builderTcpNodelay", Builder::js_tcp_nodelay)?;
+    cx.export_function(
+        "builderHttp2KeepAliveInterval",
+        Builder::js_http2_keep_alive_interval,
+    )?;
+    cx.export_function(
+        "builderHttp2KeepAliveTimeout",
+        Builder::js_http2_keep_alive_timeout,
+    )?;
+    cx.export_function(
+        "builderHttp2KeepAliveWhileIdle",
+        Builder::js_http2_keep_alive_while_idle,
+    )?;
+    cx.export_function("builderMinTlsVersion", Builder::js_min_tls_version)?;
+    cx.export_function("builderMaxTlsVersion", Builder::js_max_tls_version)?;
+    cx.export_function("builderHttp1Only", Builder::js_http1_only)?;
+    cx.export_function("builderHttp2PriorKnowledge", Builder::js_http2_prior_knowledge)?;
+    cx.export_function("builderAlpnProtocols", Builder::js_alpn_protocols)?;
     cx.export_function("builderProxy", Builder::js_proxy)?;
+    cx.export_function("builderProxyConfig", Builder::js_proxy_config)?;
+    cx.export_function("builderSocksRemoteDns", Builder::js_socks_remote_dns)?;
+    cx.export_function("builderProxyMode", Builder::js_proxy_mode)?;
+    cx.export_function("builderPac", Builder::js_pac)?;
+    cx.export_function("builderProxyPool", Builder::js_proxy_pool)?;
+    cx.export_function("builderHosts", Builder::js_hosts)?;
+    cx.export_function("builderDnsResolver", Builder::js_dns_resolver)?;
+    cx.export_function("builderDoh", Builder::js_doh)?;
+    cx.export_function("builderIpFamily", Builder::js_ip_family)?;
+    cx.export_function("builderLocalAddress", Builder::js_local_address)?;
+    cx.export_function("builderSocketPath", Builder::js_socket_path)?;
+    cx.export_function("builderRuntime", Builder::js_runtime)?;
+    cx.export_function("builderAcceptInvalidCerts", Builder::js_accept_invalid_certs)?;
+    cx.export_function("builderAcceptInvalidHostnames", Builder::js_accept_invalid_hostnames)?;
+    cx.export_function("builderRootCertificate", Builder::js_root_certificate)?;
+    cx.export_function("builderTlsBuiltInRootCerts", Builder::js_tls_built_in_root_certs)?;
+    cx.export_function("builderIdentityPkcs12", Builder::js_identity_pkcs12)?;
+    cx.export_function("builderIdentityPem", Builder::js_identity_pem)?;
+    cx.export_function("builderTlsCipherSuites", Builder::js_tls_cipher_suites)?;
+    cx.export_function("builderTlsKeyExchangeGroups", Builder::js_tls_key_exchange_groups)?;
+    cx.export_function("builderTlsSignatureAlgorithms", Builder::js_tls_signature_algorithms)?;
+    cx.export_function("builderCertificatePins", Builder::js_certificate_pins)?;
     cx.export_function("builderLogLevel", Builder::js_log_level)?;
     cx.export_function("builderBuild", Builder::js_build)?;
 
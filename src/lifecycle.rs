@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{watch, Notify};
+
+/// Tracks the requests in flight on a client, so closing it can drain or abort them.
+pub struct Lifecycle {
+    closed: AtomicBool,
+
+    in_flight: AtomicUsize,
+
+    drained: Notify,
+
+    abort: watch::Sender<bool>,
+}
+
+/// Held by a request until its callback is queued.
+pub struct InFlight(Arc<Lifecycle>);
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self {
+            closed: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            drained: Notify::new(),
+            abort: watch::channel(false).0,
+        }
+    }
+
+    /// Registers a request, `None` once the client is closed.
+    pub fn start(self: &Arc<Self>) -> Option<InFlight> {
+        if self.closed.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+
+        Some(InFlight(self.clone()))
+    }
+
+    /// Stops accepting requests.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// Makes requests in flight fail with `RequestError::Closed`.
+    pub fn abort(&self) {
+        self.abort.send_replace(true);
+    }
+
+    /// Completes once no request is in flight.
+    pub async fn drained(&self) {
+        loop {
+            // Created first, so a request finishing before the check still wakes it.
+            let notified = self.drained.notified();
+
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Completes once requests in flight are aborted.
+    pub async fn aborted(&self) {
+        self.abort.subscribe().wait_for(|abort| *abort).await.ok();
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+    }
+}
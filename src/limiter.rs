@@ -0,0 +1,265 @@
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+/// Order requests waiting for a slot are started in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum QueueOrder {
+    #[default]
+    Fifo,
+
+    /// Highest priority first, FIFO among equal priorities.
+    Priority,
+}
+
+impl FromStr for QueueOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fifo" => Ok(QueueOrder::Fifo),
+            "priority" => Ok(QueueOrder::Priority),
+            _ => Err(format!(
+                "Invalid queue order: {}, expected fifo or priority",
+                value
+            )),
+        }
+    }
+}
+
+/// Requests in flight and waiting, for the client or one origin.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub in_flight: usize,
+
+    pub queued: usize,
+}
+
+struct Waiter {
+    id: u64,
+
+    origin: String,
+
+    priority: i32,
+
+    start: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct State {
+    in_flight: usize,
+
+    /// Requests in flight per origin, without origins left idle.
+    origins: HashMap<String, usize>,
+
+    queue: VecDeque<Waiter>,
+
+    next_id: u64,
+}
+
+impl State {
+    fn has_room(&self, limiter: &Limiter, origin: &str) -> bool {
+        limiter.max.map_or(true, |max| self.in_flight < max)
+            && limiter
+                .max_per_origin
+                .map_or(true, |max| self.origins.get(origin).copied().unwrap_or(0) < max)
+    }
+
+    fn start(&mut self, origin: &str) {
+        self.in_flight += 1;
+        *self.origins.entry(origin.to_string()).or_insert(0) += 1;
+    }
+
+    fn finish(&mut self, origin: &str) {
+        self.in_flight -= 1;
+
+        if let Some(count) = self.origins.get_mut(origin) {
+            *count -= 1;
+
+            if *count == 0 {
+                self.origins.remove(origin);
+            }
+        }
+    }
+}
+
+/// Limits requests in flight, globally and per origin, queueing the rest.
+#[derive(Default)]
+pub struct Limiter {
+    max: Option<usize>,
+
+    max_per_origin: Option<usize>,
+
+    order: QueueOrder,
+
+    state: Mutex<State>,
+}
+
+/// Slot of a request in flight, freed when dropped.
+pub struct Permit {
+    limiter: Arc<Limiter>,
+
+    origin: String,
+}
+
+/// Removes a request from the queue if it stops waiting, such as when aborted.
+struct Waiting<'a> {
+    limiter: &'a Arc<Limiter>,
+
+    id: u64,
+
+    origin: &'a str,
+
+    started: bool,
+}
+
+impl Limiter {
+    pub fn new(max: Option<usize>, max_per_origin: Option<usize>, order: QueueOrder) -> Self {
+        Self {
+            max,
+            max_per_origin,
+            order,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Waits for a slot for a request to `origin`, with `priority` only used by the priority order.
+    pub async fn acquire(self: &Arc<Self>, origin: &str, priority: i32) -> Permit {
+        let (id, started) = {
+            let mut state = self.state.lock().unwrap();
+
+            // Queued requests start as soon as they have room, so this one starting right away
+            // never overtakes a request that could have started.
+            if state.has_room(self, origin) {
+                state.start(origin);
+
+                return self.permit(origin);
+            }
+
+            let (start, started) = oneshot::channel();
+
+            let id = state.next_id;
+            state.next_id += 1;
+
+            let waiter = Waiter {
+                id,
+                origin: origin.to_string(),
+                priority,
+                start,
+            };
+
+            let pos = match self.order {
+                QueueOrder::Fifo => state.queue.len(),
+                QueueOrder::Priority => state
+                    .queue
+                    .iter()
+                    .position(|w| w.priority < priority)
+                    .unwrap_or(state.queue.len()),
+            };
+
+            state.queue.insert(pos, waiter);
+
+            (id, started)
+        };
+
+        let mut waiting = Waiting {
+            limiter: self,
+            id,
+            origin,
+            started: false,
+        };
+
+        // The sender is only dropped once the slot is taken.
+        started.await.ok();
+
+        waiting.started = true;
+
+        self.permit(origin)
+    }
+
+    fn permit(self: &Arc<Self>, origin: &str) -> Permit {
+        Permit {
+            limiter: self.clone(),
+            origin: origin.to_string(),
+        }
+    }
+
+    /// Starts queued requests while there is room, in queue order.
+    fn dispatch(&self, state: &mut State) {
+        let mut i = 0;
+
+        while i < state.queue.len() && self.max.map_or(true, |max| state.in_flight < max) {
+            if state.has_room(self, &state.queue[i].origin) {
+                let waiter = state.queue.remove(i).unwrap();
+
+                state.start(&waiter.origin);
+                waiter.start.send(()).ok();
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    pub fn counts(&self) -> (Counts, HashMap<String, Counts>) {
+        let state = self.state.lock().unwrap();
+
+        let mut origins: HashMap<String, Counts> = state
+            .origins
+            .iter()
+            .map(|(origin, in_flight)| {
+                (
+                    origin.clone(),
+                    Counts {
+                        in_flight: *in_flight,
+                        queued: 0,
+                    },
+                )
+            })
+            .collect();
+
+        for waiter in &state.queue {
+            origins.entry(waiter.origin.clone()).or_default().queued += 1;
+        }
+
+        let total = Counts {
+            in_flight: state.in_flight,
+            queued: state.queue.len(),
+        };
+
+        (total, origins)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+
+        state.finish(&self.origin);
+
+        self.limiter.dispatch(&mut state);
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.started {
+            return;
+        }
+
+        let mut state = self.limiter.state.lock().unwrap();
+
+        match state.queue.iter().position(|w| w.id == self.id) {
+            Some(pos) => {
+                state.queue.remove(pos);
+            }
+            // Started meanwhile, the slot is given back.
+            None => {
+                state.finish(self.origin);
+
+                self.limiter.dispatch(&mut state);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use log::{debug, warn};
+
+use sha2::{Digest, Sha256};
+
+pub type Pin = [u8; 32];
+
+const MISMATCH: &str = " does not match any pinned public key";
+
+/// The certificate chain presented by a pinned host matched none of its pins.
+#[derive(Debug)]
+pub struct PinError {
+    pub host: String,
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Certificate for {}{}", self.host, MISMATCH)
+    }
+}
+
+impl PinError {
+    /// Finds a pin mismatch behind a request error.
+    ///
+    /// rustls only carries verifier errors as text, wrapped in the I/O error of the handshake.
+    pub fn find(e: &(dyn StdError + 'static)) -> Option<Self> {
+        let mut source = Some(e);
+
+        while let Some(e) = source {
+            let inner = e
+                .downcast_ref::<io::Error>()
+                .and_then(|e| e.get_ref())
+                .map_or(e, |inner| inner as &(dyn StdError + 'static));
+
+            if let Some(rustls::Error::General(message)) = inner.downcast_ref::<rustls::Error>() {
+                if let Some(host) = message
+                    .strip_prefix("Certificate for ")
+                    .and_then(|v| v.strip_suffix(MISMATCH))
+                {
+                    return Some(PinError {
+                        host: host.to_owned(),
+                    });
+                }
+            }
+
+            source = e.source();
+        }
+
+        None
+    }
+}
+
+/// SPKI SHA-256 pins per hostname.
+///
+/// Checked by the TLS verifier on every handshake, after chain validation, so each connection
+/// a request uses is verified, those of redirects included. A pin may be for the key of any
+/// certificate of the chain presented.
+pub struct Pins {
+    hosts: HashMap<String, HashSet<Pin>>,
+
+    report_only: bool,
+}
+
+impl Pins {
+    pub fn new(report_only: bool) -> Self {
+        Self {
+            hosts: HashMap::new(),
+            report_only,
+        }
+    }
+
+    /// Adds a pin for `host`, either as `sha256/<base64>` or bare base64.
+    pub fn add(&mut self, host: &str, pin: &str) -> Result<(), String> {
+        let encoded = pin.strip_prefix("sha256/").unwrap_or(pin);
+
+        let pin: Pin = BASE64
+            .decode(encoded)
+            .ok()
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(|| format!("Invalid pin for {}: {}", host, pin))?;
+
+        self.hosts
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .insert(pin);
+
+        Ok(())
+    }
+
+    /// Checks the DER certificates presented by `host` if it is pinned.
+    pub fn check(&self, host: &str, chain: &[&[u8]]) -> Result<(), PinError> {
+        let host = host.to_ascii_lowercase();
+
+        let pins = match self.hosts.get(&host) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let matched = chain
+            .iter()
+            .filter_map(|cert| spki(cert))
+            .any(|spki| {
+                let pin: Pin = Sha256::digest(spki).into();
+
+                pins.contains(&pin)
+            });
+
+        if matched {
+            debug!("Certificate pin verified for {}", &host);
+
+            return Ok(());
+        }
+
+        let e = PinError { host };
+
+        if self.report_only {
+            warn!("{} (report only)", e);
+
+            return Ok(());
+        }
+
+        Err(e)
+    }
+}
+
+/// Reads a DER element, returning its tag, content, and the remaining input.
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+
+    let (len, header) = match first {
+        0..=0x7f => (first, 2),
+        0x81..=0x84 => {
+            let n = first & 0x7f;
+            let len = data
+                .get(2..2 + n)?
+                .iter()
+                .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+
+            (len, 2 + n)
+        }
+        _ => return None,
+    };
+
+    let content = data.get(header..header + len)?;
+
+    Some((tag, content, &data[header + len..]))
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of a X.509 certificate.
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der_element(cert)?;
+    let (_, mut tbs, _) = der_element(cert)?;
+
+    // Explicitly tagged version, absent for v1 certificates.
+    if *tbs.first()? == 0xa0 {
+        tbs = der_element(tbs)?.2;
+    }
+
+    // serialNumber, signature, issuer, validity, subject.
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.2;
+    }
+
+    let (_, _, rest) = der_element(tbs)?;
+
+    Some(&tbs[..tbs.len() - rest.len()])
+}
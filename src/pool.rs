@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::{Client as ReqwestClient, Url, Version};
+
+use crate::builder::ClientSteps;
+
+/// Connection pools per origin.
+///
+/// reqwest neither lists nor partially closes its pool, so each origin gets a client of its
+/// own, built from the recorded steps and sharing what they capture, such as the cookie jar,
+/// proxy rules and resolvers. Redirects to other origins stay on the pool of the origin first
+/// requested. Requests without an origin, including Unix socket requests, use a shared client
+/// that opens no TCP connections of its own.
+pub struct OriginPools {
+    steps: ClientSteps,
+
+    /// Pools idle for longer hold no connections anymore and are dropped when adding another.
+    idle_timeout: Option<Duration>,
+
+    state: Mutex<State>,
+}
+
+struct State {
+    fallback: ReqwestClient,
+
+    pools: HashMap<String, OriginPool>,
+
+    next_id: u64,
+}
+
+struct OriginPool {
+    /// Tells a pool apart from the one replacing it after a close or reset.
+    id: u64,
+
+    client: ReqwestClient,
+
+    in_flight: usize,
+
+    version: Option<Version>,
+
+    used: Instant,
+}
+
+/// State of the pool of an origin.
+#[derive(Debug)]
+pub struct PoolInfo {
+    pub origin: String,
+
+    pub in_flight: usize,
+
+    /// Protocol of the last response, none before the first.
+    pub version: Option<Version>,
+
+    /// Time since the last request completed, none while requests are in flight.
+    pub idle: Option<Duration>,
+}
+
+/// Client of an origin for one request, counted in flight until dropped.
+pub struct Checkout {
+    pools: Arc<OriginPools>,
+
+    key: Option<(String, u64)>,
+
+    client: ReqwestClient,
+}
+
+impl OriginPools {
+    /// `fallback` is a client already built from `steps`.
+    pub fn new(
+        steps: ClientSteps,
+        fallback: ReqwestClient,
+        idle_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            steps,
+            idle_timeout,
+            state: Mutex::new(State {
+                fallback,
+                pools: HashMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Client for a request to `url`, creating the pool of its origin on first use.
+    pub fn checkout(self: &Arc<Self>, url: Option<&Url>) -> reqwest::Result<Checkout> {
+        let origin = url
+            .map(Url::origin)
+            .filter(|origin| origin.is_tuple())
+            .map(|origin| origin.ascii_serialization());
+
+        let mut state = self.state.lock().unwrap();
+
+        let origin = match origin {
+            Some(v) => v,
+            None => {
+                return Ok(Checkout {
+                    pools: self.clone(),
+                    key: None,
+                    client: state.fallback.clone(),
+                })
+            }
+        };
+
+        let now = Instant::now();
+
+        if !state.pools.contains_key(&origin) {
+            if let Some(timeout) = self.idle_timeout {
+                state.pools.retain(|_, pool| {
+                    pool.in_flight > 0 || now.duration_since(pool.used) < timeout
+                });
+            }
+
+            let client = self.steps.build()?;
+
+            state.next_id += 1;
+
+            let id = state.next_id;
+
+            state.pools.insert(
+                origin.clone(),
+                OriginPool {
+                    id,
+                    client,
+                    in_flight: 0,
+                    version: None,
+                    used: now,
+                },
+            );
+        }
+
+        let pool = state.pools.get_mut(&origin).unwrap();
+
+        pool.in_flight += 1;
+        pool.used = now;
+
+        Ok(Checkout {
+            pools: self.clone(),
+            key: Some((origin, pool.id)),
+            client: pool.client.clone(),
+        })
+    }
+
+    /// Pools by origin, most recently used first.
+    pub fn list(&self) -> Vec<PoolInfo> {
+        let state = self.state.lock().unwrap();
+
+        let mut pools: Vec<_> = state.pools.iter().collect();
+        pools.sort_by_key(|(_, pool)| std::cmp::Reverse(pool.used));
+
+        pools
+            .into_iter()
+            .map(|(origin, pool)| PoolInfo {
+                origin: origin.clone(),
+                in_flight: pool.in_flight,
+                version: pool.version,
+                idle: (pool.in_flight == 0).then(|| pool.used.elapsed()),
+            })
+            .collect()
+    }
+
+    /// Drops the pool of an origin, returning whether there was one.
+    ///
+    /// Idle connections close right away, unless requests are in flight on the pool, in which
+    /// case they close along with the connections in use once those requests complete.
+    pub fn close(&self, origin: &str) -> bool {
+        self.state.lock().unwrap().pools.remove(origin).is_some()
+    }
+
+    /// Drops every pool, see [`OriginPools::close`].
+    pub fn reset(&self) -> reqwest::Result<()> {
+        let fallback = self.steps.build()?;
+
+        let mut state = self.state.lock().unwrap();
+
+        state.fallback = fallback;
+        state.pools.clear();
+
+        Ok(())
+    }
+}
+
+impl Checkout {
+    pub fn client(&self) -> &ReqwestClient {
+        &self.client
+    }
+
+    /// Records the protocol of a response, unless it came from another origin after redirects.
+    pub fn record(&self, url: &Url, version: Version) {
+        let (origin, id) = match &self.key {
+            Some(v) => v,
+            None => return,
+        };
+
+        if url.origin().ascii_serialization() != *origin {
+            return;
+        }
+
+        let mut state = self.pools.state.lock().unwrap();
+
+        if let Some(pool) = state.pools.get_mut(origin).filter(|pool| pool.id == *id) {
+            pool.version = Some(version);
+        }
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        let (origin, id) = match &self.key {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mut state = self.pools.state.lock().unwrap();
+
+        if let Some(pool) = state.pools.get_mut(origin).filter(|pool| pool.id == *id) {
+            pool.in_flight -= 1;
+            pool.used = Instant::now();
+        }
+    }
+}
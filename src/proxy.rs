@@ -0,0 +1,505 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+
+use reqwest::{Error, Response, Url};
+
+use crate::bridge::ProxyBridge;
+
+tokio::task_local! {
+    /// Proxy picked from the pool for the request attempt being polled.
+    ///
+    /// reqwest resolves proxies while connecting, within the task sending the request.
+    static POOL_PROXY: Url;
+}
+
+/// A redirect led to a host the PAC script has not been evaluated for yet.
+///
+/// Raised by the redirect policy so the request fails rather than connecting directly.
+#[derive(Debug)]
+pub struct PacUnresolved {
+    pub url: Url,
+}
+
+impl fmt::Display for PacUnresolved {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No PAC result for redirect to {}", self.url)
+    }
+}
+
+impl StdError for PacUnresolved {}
+
+impl PacUnresolved {
+    /// Finds the unresolved redirect behind a request error.
+    pub fn find(e: &(dyn StdError + 'static)) -> Option<&Self> {
+        let mut source = Some(e);
+
+        while let Some(e) = source {
+            if let Some(unresolved) = e.downcast_ref::<Self>() {
+                return Some(unresolved);
+            }
+
+            source = e.source();
+        }
+
+        None
+    }
+}
+
+/// A `NO_PROXY` style bypass entry.
+#[derive(Debug)]
+pub enum Bypass {
+    /// `*`, bypasses every host.
+    All,
+
+    /// `example.com` or `.example.com`, the domain and its subdomains.
+    Domain(String),
+
+    /// `*.example.com`, subdomains only.
+    Wildcard(String),
+
+    /// `10.0.0.0/8`, literal IP hosts within the network.
+    Cidr(IpAddr, u8),
+}
+
+impl Bypass {
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let entry = entry.trim().to_ascii_lowercase();
+
+        if entry == "*" {
+            return Ok(Bypass::All);
+        }
+
+        if let Some(domain) = entry.strip_prefix("*.") {
+            return Ok(Bypass::Wildcard(format!(".{}", domain)));
+        }
+
+        let (addr, prefix) = match entry.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (entry.as_str(), None),
+        };
+
+        let addr = addr.trim_start_matches('[').trim_end_matches(']');
+
+        match (addr.parse::<IpAddr>(), prefix) {
+            (Ok(ip), prefix) => {
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+
+                let prefix = match prefix {
+                    Some(v) => v
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|v| *v <= max)
+                        .ok_or_else(|| format!("Invalid proxy bypass entry: {}", entry))?,
+                    None => max,
+                };
+
+                Ok(Bypass::Cidr(ip, prefix))
+            }
+            (Err(_), Some(_)) => Err(format!("Invalid proxy bypass entry: {}", entry)),
+            (Err(_), None) if entry.is_empty() => {
+                Err(format!("Invalid proxy bypass entry: {}", entry))
+            }
+            (Err(_), None) => Ok(Bypass::Domain(entry.trim_start_matches('.').to_owned())),
+        }
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(v) => v.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase(),
+            None => return false,
+        };
+
+        match self {
+            Bypass::All => true,
+            Bypass::Domain(domain) => {
+                host == *domain
+                    || (host.ends_with(domain.as_str())
+                        && host[..host.len() - domain.len()].ends_with('.'))
+            }
+            Bypass::Wildcard(suffix) => host.ends_with(suffix.as_str()),
+            Bypass::Cidr(network, prefix) => match host.parse::<IpAddr>() {
+                Ok(ip) => in_network(ip, *network, *prefix),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// How a proxy pool picks the proxy for each request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    /// Each request uses the next proxy in order.
+    RoundRobin,
+
+    /// Requests to an origin keep using the same proxy until it fails for that origin.
+    ///
+    /// Failures for other origins only put the proxy in cooldown for new assignments, as
+    /// pooled connections to the origin still go through it.
+    Sticky,
+
+    /// Each request uses a random proxy.
+    Random,
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(Rotation::RoundRobin),
+            "sticky" => Ok(Rotation::Sticky),
+            "random" => Ok(Rotation::Random),
+            _ => Err(format!("Invalid proxy rotation: {}", s)),
+        }
+    }
+}
+
+/// Proxies rotated across requests.
+///
+/// A proxy failing to connect is skipped for `cooldown`, unless every proxy of the pool failed.
+#[derive(Debug)]
+pub struct ProxyPool {
+    proxies: Vec<Url>,
+
+    rotation: Rotation,
+
+    cooldown: Duration,
+
+    next: AtomicUsize,
+
+    sticky: Mutex<HashMap<String, usize>>,
+
+    failed: Mutex<HashMap<usize, Instant>>,
+}
+
+impl ProxyPool {
+    pub fn new(proxies: Vec<Url>, rotation: Rotation, cooldown: Duration) -> Self {
+        Self {
+            proxies,
+            rotation,
+            cooldown,
+            next: AtomicUsize::new(0),
+            sticky: Mutex::new(HashMap::new()),
+            failed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Picks the proxy for a request to `url`.
+    pub fn pick(&self, url: &Url) -> Url {
+        let failed = self.failed.lock().unwrap();
+
+        let healthy = |i: &usize| {
+            failed
+                .get(i)
+                .map_or(true, |failed_at| failed_at.elapsed() >= self.cooldown)
+        };
+
+        let len = self.proxies.len();
+
+        let origin = url.origin().ascii_serialization();
+
+        let start = match self.rotation {
+            Rotation::Sticky => match self.sticky.lock().unwrap().get(&origin) {
+                Some(i) => return self.proxies[*i].clone(),
+                None => self.next.fetch_add(1, Ordering::Relaxed) % len,
+            },
+            Rotation::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % len,
+            Rotation::Random => fastrand::usize(..len),
+        };
+
+        let i = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(healthy)
+            .unwrap_or(start);
+
+        if self.rotation == Rotation::Sticky {
+            self.sticky.lock().unwrap().insert(origin, i);
+        }
+
+        self.proxies[i].clone()
+    }
+
+    pub fn mark_failed(&self, proxy: &Url) {
+        if let Some(i) = self.proxies.iter().position(|v| v == proxy) {
+            warn!(
+                "Proxy {} failed to connect, skipping it for {:?}",
+                ProxyRules::redact(proxy),
+                self.cooldown
+            );
+
+            self.failed.lock().unwrap().insert(i, Instant::now());
+        }
+    }
+
+    /// Moves the origin of `url` off `proxy` after failing to connect through it.
+    ///
+    /// The failed attempt opened a new connection, so none to the origin was pooled for reuse.
+    fn unstick(&self, url: &Url, proxy: &Url) {
+        let mut sticky = self.sticky.lock().unwrap();
+        let origin = url.origin().ascii_serialization();
+
+        if sticky.get(&origin).map_or(false, |i| self.proxies[*i] == *proxy) {
+            sticky.remove(&origin);
+        }
+    }
+
+    /// Runs a request attempt through a proxy of the pool, marking it failed on connect errors.
+    ///
+    /// `send` is called within the attempt too, as reqwest already resolves the proxy
+    /// for its credentials when a request is sent. Returns the proxy used along with the response.
+    pub async fn send<S, F>(&self, url: &Url, send: S) -> (Url, Result<Response, Error>)
+    where
+        S: FnOnce() -> F,
+        F: Future<Output = Result<Response, Error>>,
+    {
+        let proxy = self.pick(url);
+
+        debug!(
+            "Request to {} via pooled proxy {}",
+            url,
+            ProxyRules::redact(&proxy)
+        );
+
+        let res = POOL_PROXY
+            .scope(proxy.clone(), async move { send().await })
+            .await;
+
+        if matches!(&res, Err(e) if e.is_connect()) {
+            self.mark_failed(&proxy);
+            self.unstick(url, &proxy);
+        }
+
+        (proxy, res)
+    }
+}
+
+/// How the proxy for a client is chosen.
+#[derive(Debug)]
+pub enum ProxyMode {
+    /// Always connect directly.
+    None,
+
+    /// Proxies from a snapshot of the environment variables.
+    Env(ProxyRules),
+
+    /// Only the proxies configured on the builder.
+    Explicit,
+}
+
+/// Proxy selection per request scheme.
+///
+/// Passed to reqwest as a custom proxy, so the proxy reqwest connects through is
+/// always the one resolved here.
+#[derive(Debug, Default)]
+pub struct ProxyRules {
+    pub http: Option<Url>,
+
+    pub https: Option<Url>,
+
+    pub all: Option<Url>,
+
+    pub bypass: Vec<Bypass>,
+
+    /// Proxies chosen by a PAC script per `host[:port]`, replacing the rules above.
+    ///
+    /// The script is evaluated on the JS side, which fills this in before requesting a new host.
+    /// Redirects to hosts without an entry fail with [`PacUnresolved`] instead of connecting
+    /// directly, see [`ProxyRules::pac_resolved`].
+    pub pac: Option<RwLock<HashMap<String, Option<Url>>>>,
+
+    /// Proxies rotated per request, replacing the rules above.
+    pub pool: Option<ProxyPool>,
+
+    /// Resolve hostnames on SOCKS proxies, also for PAC results set later.
+    pub socks_remote_dns: bool,
+
+    /// Relays connections to SOCKS4 proxies and to HTTP proxies taking extra CONNECT headers,
+    /// which reqwest cannot connect through.
+    pub bridge: ProxyBridge,
+}
+
+impl ProxyRules {
+    /// Builds rules from `http_proxy`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY`,
+    /// lower case variables taking precedence like curl.
+    ///
+    /// Like curl, only the lower case `http_proxy` is read: CGI servers expose the `Proxy`
+    /// request header as `HTTP_PROXY`, which would let clients pick the proxy (httpoxy).
+    pub fn from_env(vars: &HashMap<String, String>) -> Result<Self, String> {
+        let var = |name: &str| {
+            let lower = vars.get(&name.to_ascii_lowercase());
+
+            if name == "HTTP_PROXY" {
+                return lower.filter(|v| !v.is_empty());
+            }
+
+            lower.or_else(|| vars.get(name)).filter(|v| !v.is_empty())
+        };
+
+        let mut rules = ProxyRules::default();
+
+        for (name, target) in [
+            ("HTTP_PROXY", &mut rules.http),
+            ("HTTPS_PROXY", &mut rules.https),
+            ("ALL_PROXY", &mut rules.all),
+        ] {
+            if let Some(proxy) = var(name) {
+                *target = Some(Self::parse_proxy(proxy, None)?);
+            }
+        }
+
+        if let Some(no_proxy) = var("NO_PROXY") {
+            for entry in no_proxy.split(',').filter(|v| !v.trim().is_empty()) {
+                rules.bypass.push(Bypass::parse(entry)?);
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Parses a proxy URL, embedding credentials so reqwest authenticates with them.
+    ///
+    /// Like reqwest, URLs without a scheme are treated as HTTP proxies. Credentials of SOCKS5
+    /// proxies are sent with username/password authentication (RFC 1929), SOCKS4 proxies only
+    /// take a user ID.
+    pub fn parse_proxy(
+        proxy: &str,
+        credentials: Option<(&str, &str)>,
+    ) -> Result<Url, String> {
+        let mut url = match Url::parse(proxy) {
+            Ok(url) if url.has_host() => url,
+            _ => Url::parse(&format!("http://{}", proxy))
+                .map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?,
+        };
+
+        match url.scheme() {
+            "http" | "https" | "socks5" | "socks5h" | "socks4" | "socks4a" => {}
+            scheme => return Err(format!("Unsupported proxy scheme: {}", scheme)),
+        }
+
+        if let Some((username, password)) = credentials {
+            url.set_username(username)
+                .and_then(|_| url.set_password(Some(password)))
+                .map_err(|_| format!("Invalid proxy credentials for {}", proxy))?;
+        }
+
+        if url.scheme().starts_with("socks4") && url.password().is_some() {
+            return Err(format!(
+                "SOCKS4 proxies only take a user ID, not a password: {}",
+                proxy
+            ));
+        }
+
+        Ok(url)
+    }
+
+    /// Makes a `socks5://` or `socks4://` proxy resolve hostnames on the proxy like
+    /// `socks5h://` and `socks4a://`.
+    pub fn remote_dns(proxy: &mut Url) {
+        match proxy.scheme() {
+            "socks5" => proxy.set_scheme("socks5h").unwrap(),
+            "socks4" => proxy.set_scheme("socks4a").unwrap(),
+            _ => {}
+        }
+    }
+
+    /// Resolves hostnames on every SOCKS proxy of the rules, see [`ProxyRules::remote_dns`].
+    pub fn set_socks_remote_dns(&mut self) {
+        self.socks_remote_dns = true;
+
+        for proxy in [&mut self.http, &mut self.https, &mut self.all] {
+            if let Some(proxy) = proxy {
+                Self::remote_dns(proxy);
+            }
+        }
+
+        if let Some(pool) = &mut self.pool {
+            pool.proxies.iter_mut().for_each(Self::remote_dns);
+        }
+    }
+
+    /// Key of PAC results, matching the JS `URL.host` of the request.
+    pub fn pac_key(url: &Url) -> Option<String> {
+        let host = url.host_str()?;
+
+        match url.port() {
+            Some(port) => Some(format!("{}:{}", host, port)),
+            None => Some(host.to_owned()),
+        }
+    }
+
+    /// Whether the PAC script has been evaluated for the host of `url`.
+    pub fn pac_resolved(&self, url: &Url) -> bool {
+        match (&self.pac, Self::pac_key(url)) {
+            (Some(pac), Some(key)) => pac.read().unwrap().contains_key(&key),
+            _ => true,
+        }
+    }
+
+    pub fn resolve(&self, url: &Url) -> Option<Url> {
+        if let Some(pool) = &self.pool {
+            // Each origin keeps its proxy on redirects too, matching its pooled connections.
+            if pool.rotation() == Rotation::Sticky {
+                return Some(pool.pick(url));
+            }
+
+            return Some(POOL_PROXY.try_with(Url::clone).unwrap_or_else(|_| pool.pick(url)));
+        }
+
+        if let Some(pac) = &self.pac {
+            let key = Self::pac_key(url)?;
+
+            return pac.read().unwrap().get(&key).cloned().flatten();
+        }
+
+        if self.bypass.iter().any(|b| b.matches(url)) {
+            return None;
+        }
+
+        let proxy = match url.scheme() {
+            "http" => self.http.as_ref(),
+            "https" => self.https.as_ref(),
+            _ => None,
+        };
+
+        proxy.or(self.all.as_ref()).cloned()
+    }
+
+    /// Proxy URL without credentials, for logs and response metadata.
+    pub fn redact(proxy: &Url) -> String {
+        let mut proxy = proxy.clone();
+
+        proxy.set_password(None).ok();
+        proxy.set_username("").ok();
+
+        proxy.to_string()
+    }
+}
@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::debug;
+
+use reqwest::Url;
+
+use tokio::sync::Notify;
+use tokio::time::{self, Instant};
+
+/// Due time of waits too long to represent, about 30 years like tokio's own.
+const FAR_FUTURE: Duration = Duration::from_secs(86400 * 365 * 30);
+
+/// Origins a rate limit applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OriginPattern {
+    /// `*`, every origin.
+    Any,
+
+    /// `https://api.example.com`, this origin only.
+    Origin(String),
+
+    /// `api.example.com`, the host with any scheme and port.
+    Host(String),
+
+    /// `*.example.com`, subdomains with any scheme and port.
+    Subdomains(String),
+}
+
+impl OriginPattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.trim().to_ascii_lowercase();
+
+        let invalid = || format!("Invalid rate limit origin: {}", pattern);
+
+        if pattern == "*" {
+            return Ok(OriginPattern::Any);
+        }
+
+        if pattern.contains("://") {
+            let url = Url::parse(&pattern).map_err(|_| invalid())?;
+
+            if url.host().is_none() || url.path() != "/" {
+                return Err(invalid());
+            }
+
+            return Ok(OriginPattern::Origin(url.origin().ascii_serialization()));
+        }
+
+        let (host, subdomains) = match pattern.strip_prefix("*.") {
+            Some(domain) => (domain, true),
+            None => (pattern.as_str(), false),
+        };
+
+        if host.is_empty() || host.contains(['/', '*', ':']) {
+            return Err(invalid());
+        }
+
+        Ok(match subdomains {
+            true => OriginPattern::Subdomains(format!(".{}", host)),
+            false => OriginPattern::Host(host.to_string()),
+        })
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+
+        match self {
+            OriginPattern::Any => true,
+            OriginPattern::Origin(origin) => url.origin().ascii_serialization() == *origin,
+            OriginPattern::Host(v) => host == v,
+            OriginPattern::Subdomains(suffix) => host.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+/// Request waiting for its token.
+#[derive(Clone, Copy)]
+struct Waiter {
+    id: u64,
+
+    /// When the tokens taken before it are refilled, moved up when one of them is refunded.
+    due: Instant,
+}
+
+/// Tokens of one origin, which may go negative for requests waiting their turn.
+struct Bucket {
+    tokens: f64,
+
+    updated: Instant,
+
+    /// Requests waiting for their token, in the order they took it.
+    waiters: Vec<Waiter>,
+}
+
+/// Token bucket limit, each origin it matches getting a bucket of its own.
+pub struct RateLimit {
+    pattern: OriginPattern,
+
+    /// Tokens added per second.
+    rate: f64,
+
+    /// Tokens a bucket holds at most, the requests allowed at once after being idle.
+    burst: f64,
+
+    buckets: Mutex<HashMap<String, Bucket>>,
+
+    next_id: AtomicU64,
+
+    /// Wakes waiting requests to check their due time after a refund.
+    refunded: Notify,
+}
+
+impl RateLimit {
+    /// Allows `requests` per `interval`, up to `burst` at once.
+    pub fn new(pattern: OriginPattern, requests: f64, interval: Duration, burst: f64) -> Self {
+        Self {
+            pattern,
+            rate: requests / interval.as_secs_f64(),
+            burst,
+            buckets: Mutex::default(),
+            next_id: AtomicU64::new(0),
+            refunded: Notify::new(),
+        }
+    }
+
+    /// Takes a token for `origin`, returning the request as a waiter when it is not due yet.
+    ///
+    /// Tokens are handed out in order, so waiting requests are not overtaken. Buckets that
+    /// refilled are dropped, a new one starting full as well.
+    fn reserve(&self, origin: &str) -> Option<Waiter> {
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        buckets.retain(|_, bucket| {
+            !bucket.waiters.is_empty() || self.refilled(bucket, now) < self.burst
+        });
+
+        let bucket = buckets.entry(origin.to_owned()).or_insert_with(|| Bucket {
+            tokens: self.burst,
+            updated: now,
+            waiters: Vec::new(),
+        });
+
+        bucket.tokens = self.refilled(bucket, now) - 1.0;
+        bucket.updated = now;
+
+        if bucket.tokens >= 0.0 {
+            return None;
+        }
+
+        let wait = Duration::try_from_secs_f64(-bucket.tokens / self.rate).unwrap_or(Duration::MAX);
+
+        let waiter = Waiter {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            due: now.checked_add(wait).unwrap_or_else(|| now + FAR_FUTURE),
+        };
+
+        bucket.waiters.push(waiter);
+
+        Some(waiter)
+    }
+
+    /// Tokens of a bucket at `now`.
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+
+    /// Current due time of a waiting request.
+    fn due(&self, origin: &str, id: u64) -> Option<Instant> {
+        let buckets = self.buckets.lock().unwrap();
+
+        let waiters = &buckets.get(origin)?.waiters;
+
+        waiters.iter().find(|waiter| waiter.id == id).map(|waiter| waiter.due)
+    }
+
+    /// Removes a request from the waiters once it got its token, or gives it back when it stopped
+    /// waiting first, moving up the requests waiting after it.
+    fn release(&self, origin: &str, id: u64, refund: bool) {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let bucket = match buckets.get_mut(origin) {
+            Some(v) => v,
+            None => return,
+        };
+
+        let index = match bucket.waiters.iter().position(|waiter| waiter.id == id) {
+            Some(v) => v,
+            None => return,
+        };
+
+        bucket.waiters.remove(index);
+
+        if !refund {
+            return;
+        }
+
+        bucket.tokens = (bucket.tokens + 1.0).min(self.burst);
+
+        let step = Duration::try_from_secs_f64(1.0 / self.rate).unwrap_or(Duration::MAX);
+
+        for waiter in &mut bucket.waiters[index..] {
+            waiter.due = waiter.due.checked_sub(step).unwrap_or(waiter.due);
+        }
+
+        drop(buckets);
+
+        self.refunded.notify_waiters();
+    }
+}
+
+/// Token taken by a request waiting for it to be due, refunded if the request is dropped first.
+struct Reservation<'a> {
+    limit: &'a RateLimit,
+
+    origin: String,
+
+    id: u64,
+
+    due: bool,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.due {
+            debug!("Refunding the rate limit token of a cancelled request to {}", self.origin);
+        }
+
+        self.limit.release(&self.origin, self.id, !self.due);
+    }
+}
+
+/// Rate limits of a client, the first one matching a request applying to it.
+#[derive(Default)]
+pub struct RateLimiter {
+    limits: Vec<RateLimit>,
+}
+
+impl RateLimiter {
+    pub fn push(&mut self, limit: RateLimit) {
+        self.limits.push(limit);
+    }
+
+    /// Waits for a token to send a request to `url`, returning the time waited.
+    pub async fn acquire(&self, url: &Url) -> Duration {
+        let limit = match self.limits.iter().find(|limit| limit.pattern.matches(url)) {
+            Some(v) => v,
+            None => return Duration::ZERO,
+        };
+
+        let origin = url.origin().ascii_serialization();
+
+        let start = Instant::now();
+
+        let waiter = match limit.reserve(&origin) {
+            Some(v) => v,
+            None => return Duration::ZERO,
+        };
+
+        debug!("Request to {} rate limited for {:?}", url, waiter.due - start);
+
+        let mut reservation = Reservation {
+            limit,
+            origin,
+            id: waiter.id,
+            due: false,
+        };
+
+        loop {
+            // Created before reading the due time, so a refund in between still wakes it.
+            let refunded = limit.refunded.notified();
+
+            let due = match limit.due(&reservation.origin, reservation.id) {
+                Some(v) if v > Instant::now() => v,
+                _ => break,
+            };
+
+            tokio::select! {
+                _ = time::sleep_until(due) => {}
+                _ = refunded => {}
+            }
+        }
+
+        reservation.due = true;
+
+        start.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn limiter(pattern: &str, requests: f64, interval: f64, burst: f64) -> RateLimiter {
+        let mut limiter = RateLimiter::default();
+
+        limiter.push(RateLimit::new(
+            OriginPattern::parse(pattern).unwrap(),
+            requests,
+            Duration::from_secs_f64(interval),
+            burst,
+        ));
+
+        limiter
+    }
+
+    /// Milliseconds since the first of `count` requests at which each gets its token.
+    async fn send_times(limiter: &RateLimiter, target: &Url, count: usize) -> Vec<u128> {
+        let start = Instant::now();
+        let mut times = Vec::new();
+
+        for _ in 0..count {
+            limiter.acquire(target).await;
+
+            times.push((Instant::now() - start).as_millis());
+        }
+
+        times
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!(OriginPattern::parse("*"), Ok(OriginPattern::Any));
+        assert_eq!(
+            OriginPattern::parse("https://API.example.com"),
+            Ok(OriginPattern::Origin("https://api.example.com".into()))
+        );
+        assert_eq!(
+            OriginPattern::parse("http://example.com:8080/"),
+            Ok(OriginPattern::Origin("http://example.com:8080".into()))
+        );
+        assert_eq!(
+            OriginPattern::parse("api.example.com"),
+            Ok(OriginPattern::Host("api.example.com".into()))
+        );
+        assert_eq!(
+            OriginPattern::parse("*.example.com"),
+            Ok(OriginPattern::Subdomains(".example.com".into()))
+        );
+
+        assert!(OriginPattern::parse("").is_err());
+        assert!(OriginPattern::parse("https://example.com/path").is_err());
+        assert!(OriginPattern::parse("example.com:443").is_err());
+        assert!(OriginPattern::parse("a.*.example.com").is_err());
+        assert!(OriginPattern::parse("*.").is_err());
+    }
+
+    #[test]
+    fn matches_origins() {
+        let origin = OriginPattern::parse("https://api.example.com").unwrap();
+
+        assert!(origin.matches(&url("https://api.example.com/v1")));
+        assert!(origin.matches(&url("https://api.example.com:443/")));
+        assert!(!origin.matches(&url("http://api.example.com/")));
+        assert!(!origin.matches(&url("https://api.example.com:8443/")));
+
+        let host = OriginPattern::parse("api.example.com").unwrap();
+
+        assert!(host.matches(&url("http://api.example.com:8080/")));
+        assert!(!host.matches(&url("https://www.api.example.com/")));
+
+        let subdomains = OriginPattern::parse("*.example.com").unwrap();
+
+        assert!(subdomains.matches(&url("https://a.b.example.com/")));
+        assert!(!subdomains.matches(&url("https://example.com/")));
+        assert!(!subdomains.matches(&url("https://badexample.com/")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn allows_a_burst_then_the_rate() {
+        let limiter = limiter("*", 2.0, 1.0, 3.0);
+
+        let times = send_times(&limiter, &url("https://example.com/"), 6).await;
+
+        assert_eq!(times, [0, 0, 0, 500, 1000, 1500]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refills_up_to_the_burst() {
+        let limiter = limiter("*", 1.0, 1.0, 2.0);
+        let target = url("https://example.com/");
+
+        send_times(&limiter, &target, 2).await;
+
+        time::advance(Duration::from_secs(10)).await;
+
+        assert_eq!(send_times(&limiter, &target, 3).await, [0, 0, 1000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_waiting_requests_in_order() {
+        let limiter = std::sync::Arc::new(limiter("*", 1.0, 2.0, 1.0));
+        let target = url("https://example.com/");
+
+        let start = Instant::now();
+
+        let tasks: Vec<_> = (0..3)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let target = target.clone();
+
+                tokio::spawn(async move {
+                    limiter.acquire(&target).await;
+
+                    (Instant::now() - start).as_millis()
+                })
+            })
+            .collect();
+
+        let mut times = Vec::new();
+
+        for task in tasks {
+            times.push(task.await.unwrap());
+        }
+
+        // Tasks run in the order spawned, each getting the token after the previous one.
+        assert_eq!(times, [0, 2000, 4000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refunds_cancelled_waits() {
+        let limiter = limiter("*", 1.0, 1.0, 1.0);
+        let target = url("https://example.com/");
+
+        limiter.acquire(&target).await;
+
+        assert!(time::timeout(Duration::ZERO, limiter.acquire(&target)).await.is_err());
+
+        assert_eq!(limiter.acquire(&target).await, Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn moves_up_requests_waiting_after_a_cancelled_one() {
+        let limiter = std::sync::Arc::new(limiter("*", 1.0, 1.0, 1.0));
+        let target = url("https://example.com/");
+
+        let start = Instant::now();
+
+        let spawn = || {
+            let limiter = limiter.clone();
+            let target = target.clone();
+
+            tokio::spawn(async move {
+                limiter.acquire(&target).await;
+
+                (Instant::now() - start).as_millis()
+            })
+        };
+
+        limiter.acquire(&target).await;
+
+        let first = spawn();
+        let second = spawn();
+        let third = spawn();
+
+        time::sleep(Duration::from_millis(500)).await;
+
+        second.abort();
+        assert!(second.await.unwrap_err().is_cancelled());
+
+        let newcomer = spawn();
+
+        assert_eq!(first.await.unwrap(), 1000);
+        assert_eq!(third.await.unwrap(), 2000);
+        assert_eq!(newcomer.await.unwrap(), 3000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_refilled_buckets() {
+        let limiter = limiter("*", 1.0, 1.0, 1.0);
+
+        limiter.acquire(&url("https://a.example.com/")).await;
+        limiter.acquire(&url("https://b.example.com/")).await;
+
+        time::advance(Duration::from_secs(2)).await;
+
+        limiter.acquire(&url("https://c.example.com/")).await;
+
+        let buckets = limiter.limits[0].buckets.lock().unwrap();
+
+        assert_eq!(buckets.keys().collect::<Vec<_>>(), ["https://c.example.com"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_each_origin_separately() {
+        let limiter = limiter("*.example.com", 1.0, 1.0, 1.0);
+
+        let a = url("https://a.example.com/");
+        let b = url("https://b.example.com/");
+
+        assert_eq!(limiter.acquire(&a).await, Duration::ZERO);
+        assert_eq!(limiter.acquire(&b).await, Duration::ZERO);
+        assert_eq!(limiter.acquire(&a).await, Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn applies_the_first_matching_limit() {
+        let mut limiter = limiter("api.example.com", 1.0, 10.0, 1.0);
+
+        limiter.push(RateLimit::new(
+            OriginPattern::Any,
+            1.0,
+            Duration::from_secs(1),
+            1.0,
+        ));
+
+        let api = url("https://api.example.com/");
+        let other = url("https://other.test/");
+
+        limiter.acquire(&api).await;
+        assert_eq!(limiter.acquire(&api).await, Duration::from_secs(10));
+
+        limiter.acquire(&other).await;
+        assert_eq!(limiter.acquire(&other).await, Duration::from_secs(1));
+
+        let unlimited = RateLimiter::default();
+
+        assert_eq!(unlimited.acquire(&api).await, Duration::ZERO);
+    }
+}
@@ -0,0 +1,146 @@
+use std::future::Future;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// Runtime shared by every client in `RuntimeMode::Shared`, started by the first of them.
+static SHARED: OnceLock<Runtime> = OnceLock::new();
+
+/// Where a client runs its requests.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RuntimeMode {
+    /// One multi-threaded runtime for the whole process, with a worker thread per core.
+    #[default]
+    Shared,
+
+    /// A multi-threaded runtime of the client's own, with the given number of worker threads.
+    Dedicated(usize),
+
+    /// A single thread of the client's own, running both the runtime and its requests.
+    CurrentThread,
+}
+
+impl FromStr for RuntimeMode {
+    type Err = String;
+
+    /// Parses the mode, a dedicated runtime getting a worker thread per core.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "shared" => Ok(RuntimeMode::Shared),
+            "dedicated" => Ok(RuntimeMode::Dedicated(num_cpus())),
+            "current-thread" => Ok(RuntimeMode::CurrentThread),
+            _ => Err(format!(
+                "Invalid runtime: {}, expected shared, dedicated or current-thread",
+                value
+            )),
+        }
+    }
+}
+
+fn num_cpus() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Kept by a client so a runtime of its own runs until the client is dropped.
+enum Owned {
+    Dedicated(Runtime),
+
+    /// The thread stops once the sender is dropped.
+    CurrentThread(oneshot::Sender<()>),
+}
+
+pub struct ClientRuntime {
+    handle: Handle,
+
+    owned: Mutex<Option<Owned>>,
+}
+
+impl ClientRuntime {
+    pub fn new(mode: RuntimeMode) -> io::Result<Self> {
+        match mode {
+            RuntimeMode::Shared => {
+                let runtime = match SHARED.get() {
+                    Some(v) => v,
+                    None => {
+                        let runtime = Runtime::new()?;
+
+                        // Another client may have started it meanwhile, keeping the first.
+                        SHARED.get_or_init(|| runtime)
+                    }
+                };
+
+                Ok(Self {
+                    handle: runtime.handle().clone(),
+                    owned: Mutex::new(None),
+                })
+            }
+            RuntimeMode::Dedicated(threads) => {
+                let runtime = Builder::new_multi_thread()
+                    .worker_threads(threads)
+                    .enable_all()
+                    .build()?;
+
+                Ok(Self {
+                    handle: runtime.handle().clone(),
+                    owned: Mutex::new(Some(Owned::Dedicated(runtime))),
+                })
+            }
+            RuntimeMode::CurrentThread => {
+                let runtime = Builder::new_current_thread().enable_all().build()?;
+
+                let handle = runtime.handle().clone();
+
+                let (shutdown, stopped) = oneshot::channel::<()>();
+
+                // Spawned tasks only make progress while the runtime is blocked on.
+                thread::Builder::new()
+                    .name("rust-fetch".into())
+                    .spawn(move || {
+                        runtime.block_on(stopped).ok();
+                    })?;
+
+                Ok(Self {
+                    handle,
+                    owned: Mutex::new(Some(Owned::CurrentThread(shutdown))),
+                })
+            }
+        }
+    }
+
+    /// Stops a runtime of the client's own once `until` completes, then calls `done`.
+    ///
+    /// Waits in a task on the runtime itself, which cannot block on its own threads stopping,
+    /// so a dedicated runtime is shut down in the background. The shared runtime keeps running.
+    pub fn shutdown_after<F, D>(&self, until: F, done: D)
+    where
+        F: Future + Send + 'static,
+        D: FnOnce() + Send + 'static,
+    {
+        let owned = self.owned.lock().unwrap().take();
+
+        self.handle.spawn(async move {
+            until.await;
+
+            match owned {
+                Some(Owned::Dedicated(runtime)) => runtime.shutdown_background(),
+                // A current thread runtime stops once this task completes, its sender dropped.
+                other => drop(other),
+            }
+
+            done();
+        });
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+}
@@ -42,6 +42,11 @@ impl Default for TimeJar {
 
 impl CookieStore for TimeJar {
     fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        // Preconnects are not requests of the caller, whose cookies it would not expect.
+        if crate::client::preconnecting() {
+            return;
+        }
+
         let mut jar = self.0.write().unwrap();
 
         let cookies = cookie_headers.map(|h| h.to_str().unwrap().to_owned()).collect();
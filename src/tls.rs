@@ -0,0 +1,272 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use log::warn;
+
+use reqwest::tls::Version;
+
+use rustls::client::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier, WebPkiVerifier,
+};
+use rustls::internal::msgs::handshake::DigitallySignedStruct;
+use rustls::{
+    Certificate, ClientConfig, Error as TlsError, PrivateKey, RootCertStore, ServerName,
+    SignatureScheme, SupportedCipherSuite, SupportedKxGroup,
+};
+
+use crate::builder::HttpVersionPref;
+use crate::pinning::Pins;
+
+/// TLS settings kept for the rustls configuration, which is built here instead of by reqwest
+/// so certificates can be checked against pins on the connection itself, and the ClientHello
+/// shaped. rustls decides the extensions and their order.
+#[derive(Default)]
+pub struct TlsOptions {
+    pub roots: Vec<Certificate>,
+
+    pub identity: Option<(Vec<Certificate>, PrivateKey)>,
+
+    pub min_version: Option<Version>,
+
+    pub max_version: Option<Version>,
+
+    pub pins: Option<Pins>,
+
+    /// ClientHello cipher suites in order, rustls' defaults otherwise.
+    pub cipher_suites: Option<Vec<SupportedCipherSuite>>,
+
+    pub kx_groups: Option<Vec<&'static SupportedKxGroup>>,
+
+    pub signature_schemes: Option<Vec<SignatureScheme>>,
+}
+
+/// Signature algorithms verifiable by webpki, by their IANA names.
+const SIGNATURE_SCHEMES: &[(&str, SignatureScheme)] = &[
+    ("ecdsa_secp256r1_sha256", SignatureScheme::ECDSA_NISTP256_SHA256),
+    ("ecdsa_secp384r1_sha384", SignatureScheme::ECDSA_NISTP384_SHA384),
+    ("ed25519", SignatureScheme::ED25519),
+    ("rsa_pss_rsae_sha256", SignatureScheme::RSA_PSS_SHA256),
+    ("rsa_pss_rsae_sha384", SignatureScheme::RSA_PSS_SHA384),
+    ("rsa_pss_rsae_sha512", SignatureScheme::RSA_PSS_SHA512),
+    ("rsa_pkcs1_sha256", SignatureScheme::RSA_PKCS1_SHA256),
+    ("rsa_pkcs1_sha384", SignatureScheme::RSA_PKCS1_SHA384),
+    ("rsa_pkcs1_sha512", SignatureScheme::RSA_PKCS1_SHA512),
+];
+
+/// Finds a cipher suite by its IANA name, e.g. `TLS_AES_128_GCM_SHA256`.
+pub fn cipher_suite(name: &str) -> Option<SupportedCipherSuite> {
+    rustls::ALL_CIPHER_SUITES.iter().copied().find(|suite| {
+        // rustls prefixes TLS 1.3 suites with `TLS13_`.
+        let rustls_name = format!("{:?}", suite.suite());
+
+        rustls_name == name || rustls_name.replacen("TLS13_", "TLS_", 1) == name
+    })
+}
+
+/// Finds a key exchange group by name, `X25519`, `secp256r1` or `secp384r1`.
+pub fn kx_group(name: &str) -> Option<&'static SupportedKxGroup> {
+    rustls::ALL_KX_GROUPS
+        .iter()
+        .copied()
+        .find(|group| format!("{:?}", group.name) == name)
+}
+
+/// Finds a signature algorithm by its IANA name, e.g. `ecdsa_secp256r1_sha256`.
+pub fn signature_scheme(name: &str) -> Option<SignatureScheme> {
+    SIGNATURE_SCHEMES
+        .iter()
+        .find(|(v, _)| *v == name)
+        .map(|(_, scheme)| *scheme)
+}
+
+/// Reads PEM certificates, or a single DER certificate.
+pub fn parse_certificates(data: &[u8]) -> Result<Vec<Certificate>, String> {
+    const BEGIN: &[u8] = b"-----BEGIN";
+
+    if !data.windows(BEGIN.len()).any(|w| w == BEGIN) {
+        return Ok(vec![Certificate(data.to_vec())]);
+    }
+
+    match rustls_pemfile::certs(&mut Cursor::new(data)) {
+        Ok(certs) if !certs.is_empty() => Ok(certs.into_iter().map(Certificate).collect()),
+        _ => Err("No valid certificate was found".to_owned()),
+    }
+}
+
+/// Reads a private key and its certificate chain from PEM.
+pub fn parse_identity(pem: &[u8]) -> Result<(Vec<Certificate>, PrivateKey), String> {
+    let items = rustls_pemfile::read_all(&mut Cursor::new(pem)).map_err(|e| e.to_string())?;
+
+    let mut certs = Vec::new();
+    let mut key = None;
+
+    for item in items {
+        match item {
+            rustls_pemfile::Item::X509Certificate(v) => certs.push(Certificate(v)),
+            rustls_pemfile::Item::RSAKey(v)
+            | rustls_pemfile::Item::PKCS8Key(v)
+            | rustls_pemfile::Item::ECKey(v) => key = Some(PrivateKey(v)),
+            _ => {}
+        }
+    }
+
+    match (key, certs.is_empty()) {
+        (Some(key), false) => Ok((certs, key)),
+        (None, _) => Err("No private key was found".to_owned()),
+        (_, true) => Err("No certificate was found".to_owned()),
+    }
+}
+
+/// Chain validation, unless invalid certificates are accepted, followed by the pins of the host.
+struct Verifier {
+    webpki: WebPkiVerifier,
+
+    accept_invalid_certs: bool,
+
+    pins: Option<Pins>,
+
+    /// Signature algorithms offered in the ClientHello, in order.
+    schemes: Option<Vec<SignatureScheme>>,
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        if !self.accept_invalid_certs {
+            self.webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        if let Some(pins) = &self.pins {
+            let host = match server_name {
+                ServerName::DnsName(name) => name.as_ref().to_owned(),
+                ServerName::IpAddress(ip) => ip.to_string(),
+                _ => return Ok(ServerCertVerified::assertion()),
+            };
+
+            let chain: Vec<&[u8]> = std::iter::once(end_entity)
+                .chain(intermediates)
+                .map(|cert| cert.0.as_slice())
+                .collect();
+
+            pins.check(&host, &chain)
+                .map_err(|e| TlsError::General(e.to_string()))?;
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        match self.accept_invalid_certs {
+            true => Ok(HandshakeSignatureValid::assertion()),
+            false => self.webpki.verify_tls12_signature(message, cert, dss),
+        }
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        match self.accept_invalid_certs {
+            true => Ok(HandshakeSignatureValid::assertion()),
+            false => self.webpki.verify_tls13_signature(message, cert, dss),
+        }
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        match &self.schemes {
+            Some(schemes) => schemes.clone(),
+            None => self.webpki.supported_verify_schemes(),
+        }
+    }
+}
+
+impl TlsOptions {
+    /// Builds the configuration reqwest would, with certificates checked by `Verifier`.
+    pub fn client_config(
+        self,
+        accept_invalid_certs: bool,
+        http_version: Option<HttpVersionPref>,
+    ) -> Result<ClientConfig, String> {
+        // rustls only implements TLS 1.2 and 1.3.
+        let versions: Vec<_> = [
+            (Version::TLS_1_2, &rustls::version::TLS12),
+            (Version::TLS_1_3, &rustls::version::TLS13),
+        ]
+        .iter()
+        .filter(|(v, _)| self.min_version.map_or(true, |min| *v >= min))
+        .filter(|(v, _)| self.max_version.map_or(true, |max| *v <= max))
+        .map(|(_, v)| *v)
+        .collect();
+
+        if versions.is_empty() {
+            return Err("No TLS version supported by rustls is within the configured bounds".to_owned());
+        }
+
+        let mut roots = RootCertStore::empty();
+
+        for cert in &self.roots {
+            if let Err(e) = roots.add(cert) {
+                warn!("Skipping invalid root certificate: {}", e);
+            }
+        }
+
+        let verifier = Verifier {
+            webpki: WebPkiVerifier::new(roots, None),
+            accept_invalid_certs,
+            pins: self.pins,
+            schemes: self.signature_schemes,
+        };
+
+        let cipher_suites = self
+            .cipher_suites
+            .unwrap_or_else(|| rustls::DEFAULT_CIPHER_SUITES.to_vec());
+
+        let kx_groups = self
+            .kx_groups
+            .unwrap_or_else(|| rustls::ALL_KX_GROUPS.to_vec());
+
+        let builder = ClientConfig::builder()
+            .with_cipher_suites(&cipher_suites)
+            .with_kx_groups(&kx_groups)
+            .with_protocol_versions(&versions)
+            .map_err(|e| e.to_string())?
+            .with_custom_certificate_verifier(Arc::new(verifier));
+
+        let mut config = match self.identity {
+            Some((certs, key)) => builder
+                .with_single_cert(certs, key)
+                .map_err(|e| format!("Invalid PEM identity: {}", e))?,
+            None => builder.with_no_client_auth(),
+        };
+
+        config.alpn_protocols = match http_version {
+            Some(HttpVersionPref::Http1) => vec![b"http/1.1".to_vec()],
+            Some(HttpVersionPref::Http2) => vec![b"h2".to_vec()],
+            None => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        };
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,100 @@
+use std::ffi::OsString;
+use std::future::Future;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::client::connect::{Connected, Connection};
+use hyper::service::Service;
+use hyper::Uri;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UnixStream;
+
+/// Connects to the Unix socket encoded in the host of a URI built by `socket_uri`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnixConnector;
+
+pub struct UnixConnection(UnixStream);
+
+impl Connection for UnixConnection {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for UnixConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+    }
+}
+
+impl Service<Uri> for UnixConnector {
+    type Response = UnixConnection;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<UnixConnection>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(async move {
+            let path = uri.host().and_then(decode_path).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Invalid Unix socket URI")
+            })?;
+
+            Ok(UnixConnection(UnixStream::connect(path).await?))
+        })
+    }
+}
+
+/// URI of `path_and_query` on the socket at `socket_path`.
+///
+/// The socket path is hex encoded as the host, so hyper pools connections per socket.
+pub fn socket_uri(socket_path: &str, path_and_query: &str) -> Result<Uri, hyper::http::Error> {
+    let host: String = socket_path
+        .as_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    Uri::builder()
+        .scheme("http")
+        .authority(host)
+        .path_and_query(path_and_query)
+        .build()
+}
+
+fn decode_path(host: &str) -> Option<PathBuf> {
+    let bytes = (0..host.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(host.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
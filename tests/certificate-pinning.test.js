@@ -0,0 +1,99 @@
+const fs = require('fs')
+const path = require('path')
+const http = require('http')
+const https = require('https')
+const crypto = require('crypto')
+const { Client, tlsBackend } = require('../dist')
+
+jest.setTimeout(10e3)
+
+const rustlsTest = tlsBackend === 'rustls' ? test : test.skip
+const nativeTlsTest = tlsBackend === 'native-tls' ? test : test.skip
+
+const fixture = name => fs.readFileSync(path.join(__dirname, 'fixtures', name))
+
+const pinOf = cert => {
+  const spki = new crypto.X509Certificate(cert).publicKey.export({ type: 'spki', format: 'der' })
+
+  return `sha256/${crypto.createHash('sha256').update(spki).digest('base64')}`
+}
+
+let server
+let url
+let redirectServer
+let redirectUrl
+
+beforeAll(done => {
+  redirectServer = http.createServer((_req, res) => {
+    res.writeHead(302, { location: url })
+    res.end()
+  })
+
+  redirectServer.listen(0, '127.0.0.1')
+
+  server = https.createServer({ key: fixture('localhost.key'), cert: fixture('localhost.crt') }, (_req, res) => {
+    res.end('ok')
+  })
+
+  server.listen(0, '127.0.0.1', () => {
+    url = `https://localhost:${server.address().port}`
+    redirectUrl = `http://127.0.0.1:${redirectServer.address().port}`
+    done()
+  })
+})
+
+const clientWith = (pins, reportOnly) => new Client({
+  connectTimeout: 5,
+  requestTimeout: 5,
+  rootCertificates: [fixture('localhost.crt')],
+  certificatePins: { localhost: pins },
+  certificatePinsReportOnly: reportOnly,
+})
+
+rustlsTest('Matching pin', async () => {
+  const client = clientWith([pinOf(fixture('wrong-host.crt')), pinOf(fixture('localhost.crt'))])
+
+  const ret = await client.request(url)
+
+  expect(ret.statusCode).toBe(200)
+})
+
+rustlsTest('Mismatching pin should error with a distinct code', async () => {
+  const client = clientWith([pinOf(fixture('wrong-host.crt'))])
+
+  await expect(client.request(url)).rejects.toMatchObject({ code: 'ERR_CERT_PIN_MISMATCH' })
+})
+
+rustlsTest('Mismatching pin in report only mode', async () => {
+  const client = clientWith([pinOf(fixture('wrong-host.crt'))], true)
+
+  const ret = await client.request(url)
+
+  expect(ret.statusCode).toBe(200)
+})
+
+rustlsTest('Pins should be checked on redirects', async () => {
+  const client = clientWith([pinOf(fixture('wrong-host.crt'))])
+
+  await expect(client.request(redirectUrl)).rejects.toMatchObject({ code: 'ERR_CERT_PIN_MISMATCH' })
+})
+
+rustlsTest('Pins should be checked on every connection', async () => {
+  const client = clientWith([pinOf(fixture('wrong-host.crt'))])
+
+  await expect(client.request(url)).rejects.toMatchObject({ code: 'ERR_CERT_PIN_MISMATCH' })
+  await expect(client.request(url)).rejects.toMatchObject({ code: 'ERR_CERT_PIN_MISMATCH' })
+})
+
+rustlsTest('Invalid pin should throw', () => {
+  expect(() => clientWith(['sha256/nope'])).toThrow('Invalid pin')
+})
+
+nativeTlsTest('Pinning should throw with native-tls', () => {
+  expect(() => clientWith([pinOf(fixture('localhost.crt'))])).toThrow('requires the rustls backend')
+})
+
+afterAll(() => {
+  server.close()
+  redirectServer.close()
+})
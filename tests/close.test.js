@@ -0,0 +1,95 @@
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+let hanging
+
+const serverUrl = path => `http://127.0.0.1:${server.address().port}${path}`
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+const received = () => new Promise(resolve => server.once('request', resolve))
+
+beforeAll(done => {
+  server = http.createServer((req, res) => {
+    if (req.url === '/slow') {
+      setTimeout(() => res.end('slow'), 300)
+    } else if (req.url === '/hang') {
+      hanging.push(res)
+    } else {
+      res.end('ok')
+    }
+  })
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+beforeEach(() => {
+  hanging = []
+})
+
+afterEach(() => {
+  hanging.forEach(res => res.destroy())
+})
+
+describe.each(['shared', 'dedicated', 'current-thread'])('%s runtime', runtime => {
+  test('Drains requests in flight', async () => {
+    const client = clientWith({ runtime })
+
+    const pending = client.request(serverUrl('/slow'))
+
+    await received()
+
+    await client.close()
+
+    await expect(pending).resolves.toMatchObject({ body: 'slow' })
+  })
+
+  test('Aborts requests in flight', async () => {
+    const client = clientWith({ runtime })
+
+    const pending = client.request(serverUrl('/hang'))
+
+    await received()
+
+    await client.close({ abort: true })
+
+    await expect(pending).rejects.toMatchObject({ code: 'ERR_CLIENT_CLOSED' })
+  })
+})
+
+test('Aborts requests still in flight after the timeout', async () => {
+  const client = clientWith()
+
+  const slow = client.request(serverUrl('/slow'))
+  const hang = client.request(serverUrl('/hang'))
+
+  await Promise.all([received(), received()])
+
+  await client.close({ timeout: 1 })
+
+  await expect(slow).resolves.toMatchObject({ body: 'slow' })
+  await expect(hang).rejects.toMatchObject({ code: 'ERR_CLIENT_CLOSED' })
+})
+
+test('Rejects requests once closed', async () => {
+  const client = clientWith()
+
+  const closing = client.close()
+
+  expect(client.close()).toBe(closing)
+
+  await expect(client.request(serverUrl('/'))).rejects.toMatchObject({
+    message: 'Client is closed',
+    code: 'ERR_CLIENT_CLOSED',
+  })
+
+  await closing
+
+  await expect(client.preconnect(serverUrl('/'))).rejects.toMatchObject({ code: 'ERR_CLIENT_CLOSED' })
+})
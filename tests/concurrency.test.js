@@ -0,0 +1,139 @@
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let servers
+let arrivals
+let held
+
+const sleep = ms => new Promise(resolve => setTimeout(resolve, ms))
+
+const urlOf = (server, path = '/') => `http://127.0.0.1:${server.address().port}${path}`
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+function createServer() {
+  const server = http.createServer((req, res) => {
+    arrivals.push(req.url)
+    server.active++
+    server.peak = Math.max(server.peak, server.active)
+
+    held.push(() => {
+      server.active--
+      res.end(req.url)
+    })
+  })
+
+  return server
+}
+
+async function releaseAll(pending) {
+  while (held.length || arrivals.length < pending) {
+    held.splice(0).forEach(release => release())
+    await sleep(20)
+  }
+}
+
+beforeAll(async () => {
+  servers = [createServer(), createServer()]
+
+  await Promise.all(servers.map(s => new Promise(resolve => s.listen(0, '127.0.0.1', resolve))))
+})
+
+afterAll(() => {
+  servers.forEach(s => s.close())
+})
+
+beforeEach(() => {
+  arrivals = []
+  held = []
+  servers.forEach(s => {
+    s.active = 0
+    s.peak = 0
+  })
+})
+
+test('Limits requests in flight and queues the rest', async () => {
+  const client = clientWith({ maxConcurrentRequests: 2 })
+
+  const pending = Array.from({ length: 6 }, (_, i) => client.request(urlOf(servers[i % 2], `/${i}`)))
+
+  while (arrivals.length < 2) await sleep(20)
+  await sleep(100)
+
+  expect(arrivals).toHaveLength(2)
+  expect(client.stats()).toMatchObject({ inFlight: 2, queued: 4 })
+
+  await releaseAll(6)
+
+  const responses = await Promise.all(pending)
+
+  expect(servers[0].peak + servers[1].peak).toBeLessThanOrEqual(2)
+  expect(responses[5].timings.queued).toBeGreaterThan(0)
+  expect(responses[5].timings.total).toBeGreaterThanOrEqual(responses[5].timings.queued)
+  expect(client.stats()).toEqual({ inFlight: 0, queued: 0, origins: {} })
+})
+
+test('Limits requests in flight per origin', async () => {
+  const client = clientWith({ maxConcurrentRequestsPerOrigin: 1 })
+
+  const pending = [0, 1, 2, 3].map(i => client.request(urlOf(servers[i % 2], `/${i}`)))
+
+  while (arrivals.length < 2) await sleep(20)
+  await sleep(100)
+
+  const stats = client.stats()
+
+  expect(stats).toMatchObject({ inFlight: 2, queued: 2 })
+  expect(stats.origins[urlOf(servers[0]).slice(0, -1)]).toEqual({ inFlight: 1, queued: 1 })
+
+  await releaseAll(4)
+  await Promise.all(pending)
+
+  expect(servers[0].peak).toBe(1)
+  expect(servers[1].peak).toBe(1)
+})
+
+test('Starts queued requests by priority', async () => {
+  const client = clientWith({ maxConcurrentRequests: 1, queueOrder: 'priority' })
+
+  const pending = [client.request(urlOf(servers[0], '/first'))]
+
+  while (arrivals.length < 1) await sleep(20)
+
+  pending.push(client.request(urlOf(servers[0], '/low'), { priority: -1 }))
+  pending.push(client.request(urlOf(servers[0], '/normal')))
+  pending.push(client.request(urlOf(servers[0], '/high'), { priority: 10 }))
+
+  while (client.stats().queued < 3) await sleep(20)
+
+  await releaseAll(4)
+  await Promise.all(pending)
+
+  expect(arrivals).toEqual(['/first', '/high', '/normal', '/low'])
+})
+
+test('Starts queued requests in order by default', async () => {
+  const client = clientWith({ maxConcurrentRequests: 1 })
+
+  const pending = [client.request(urlOf(servers[0], '/first'))]
+
+  while (arrivals.length < 1) await sleep(20)
+
+  pending.push(client.request(urlOf(servers[0], '/second'), { priority: -1 }))
+  pending.push(client.request(urlOf(servers[0], '/third'), { priority: 10 }))
+
+  while (client.stats().queued < 2) await sleep(20)
+
+  await releaseAll(3)
+  await Promise.all(pending)
+
+  expect(arrivals).toEqual(['/first', '/second', '/third'])
+})
+
+test('Validates limits', () => {
+  expect(() => clientWith({ maxConcurrentRequests: 0 })).toThrow('Invalid max concurrent requests: 0')
+  expect(() => clientWith({ maxConcurrentRequestsPerOrigin: 1.5 })).toThrow('Invalid max concurrent requests per origin')
+  expect(() => clientWith({ queueOrder: 'lifo' })).toThrow('Invalid queue order: lifo')
+})
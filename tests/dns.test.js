@@ -0,0 +1,134 @@
+const fs = require('fs')
+const path = require('path')
+const http = require('http')
+const https = require('https')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let target
+let tlsTarget
+let servernames
+
+function listen(server) {
+  return new Promise(resolve => server.listen(0, '127.0.0.1', () => resolve(server)))
+}
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+beforeAll(async () => {
+  target = await listen(http.createServer((req, res) => res.end(req.headers.host)))
+
+  tlsTarget = await listen(https.createServer({
+    key: fs.readFileSync(path.join(__dirname, 'fixtures/wrong-host.key')),
+    cert: fs.readFileSync(path.join(__dirname, 'fixtures/wrong-host.crt')),
+  }, (req, res) => {
+    servernames.push(req.socket.servername)
+    res.end('secure')
+  }))
+})
+
+afterAll(() => {
+  target.close()
+  tlsTarget.close()
+})
+
+beforeEach(() => {
+  servernames = []
+})
+
+test('Connects overridden hosts to their address', async () => {
+  const client = clientWith({ hosts: { 'api.example.test': '127.0.0.1' } })
+
+  const ret = await client.request(`http://api.example.test:${target.address().port}/`)
+
+  expect(ret.body).toBe(`api.example.test:${target.address().port}`)
+})
+
+test('Keeps the hostname for TLS', async () => {
+  const client = clientWith({
+    hosts: { 'wrong.host': ['127.0.0.1'] },
+    rootCertificates: [fs.readFileSync(path.join(__dirname, 'fixtures/wrong-host.crt'))],
+  })
+
+  const ret = await client.request(`https://wrong.host:${tlsTarget.address().port}/`)
+
+  expect(ret.body).toBe('secure')
+  expect(servernames).toEqual(['wrong.host'])
+})
+
+test('Rejects invalid override addresses', () => {
+  expect(() => clientWith({ hosts: { 'api.example.test': '127.0.0.1:80' } })).toThrow('Invalid address')
+})
+
+test('Resolves through a custom resolver, caching for the TTL', async () => {
+  const lookups = []
+
+  const client = clientWith({
+    dnsResolver: async hostname => {
+      lookups.push(hostname)
+
+      return [{ address: '127.0.0.1', ttl: 60 }]
+    },
+  })
+
+  for (let i = 0; i < 2; i++) {
+    const ret = await client.request(`http://cached.example.test:${target.address().port}/`)
+
+    expect(ret.body).toBe(`cached.example.test:${target.address().port}`)
+  }
+
+  expect(lookups).toEqual(['cached.example.test'])
+})
+
+test('Does not cache answers without a TTL', async () => {
+  const lookups = []
+
+  // Fresh connections, so each request resolves the host.
+  const client = clientWith({
+    dnsResolver: async hostname => {
+      lookups.push(hostname)
+
+      return [{ address: '127.0.0.1' }]
+    },
+  })
+
+  await client.request(`http://uncached.example.test:${target.address().port}/`, { headers: { Connection: 'close' } })
+  await client.request(`http://uncached.example.test:${target.address().port}/`, { headers: { Connection: 'close' } })
+
+  expect(lookups).toEqual(['uncached.example.test', 'uncached.example.test'])
+})
+
+test('Static overrides take precedence over the resolver', async () => {
+  const lookups = []
+
+  const client = clientWith({
+    hosts: { 'static.example.test': '127.0.0.1' },
+    dnsResolver: async hostname => {
+      lookups.push(hostname)
+
+      return []
+    },
+  })
+
+  const ret = await client.request(`http://static.example.test:${target.address().port}/`)
+
+  expect(ret.statusCode).toBe(200)
+  expect(lookups).toHaveLength(0)
+})
+
+test('Fails requests when the resolver fails', async () => {
+  const client = clientWith({
+    dnsResolver: async () => {
+      throw new Error('NXDOMAIN')
+    },
+  })
+
+  await expect(client.request('http://missing.example.test/', { attempts: 0 })).rejects.toThrow()
+})
+
+test('Fails requests when the resolver returns invalid addresses', async () => {
+  const client = clientWith({ dnsResolver: async () => [{ address: 'not an ip' }] })
+
+  await expect(client.request('http://invalid.example.test/', { attempts: 0 })).rejects.toThrow()
+})
@@ -0,0 +1,117 @@
+const fs = require('fs')
+const path = require('path')
+const http = require('http')
+const { Client } = require('../dist')
+const { createDohServer } = require('./helpers/doh-server')
+
+jest.setTimeout(10e3)
+
+let doh
+let target
+
+function listen(server) {
+  return new Promise(resolve => server.listen(0, '127.0.0.1', () => resolve(server)))
+}
+
+const targetUrl = host => `http://${host}:${target.address().port}/`
+
+// `wrong.host` does not resolve, so the endpoint is only reachable through its bootstrap address.
+const clientWith = options => new Client({
+  connectTimeout: 5,
+  requestTimeout: 5,
+  rootCertificates: [fs.readFileSync(path.join(__dirname, 'fixtures/wrong-host.crt'))],
+  doh: { url: `https://wrong.host:${doh.address().port}/dns-query`, bootstrap: ['127.0.0.1'], ...options },
+})
+
+beforeAll(async () => {
+  doh = await listen(createDohServer())
+  target = await listen(http.createServer((req, res) => res.end(req.headers.host)))
+})
+
+afterAll(() => {
+  doh.close()
+  target.close()
+})
+
+beforeEach(() => {
+  doh.records = {
+    'app.example.test': { A: ['127.0.0.1'], ttl: 60 },
+    'short.example.test': { A: ['127.0.0.1'], ttl: 0 },
+    'empty.example.test': {},
+  }
+  doh.queries = []
+  doh.failing = false
+  doh.failingTypes = []
+})
+
+test('Resolves hostnames over HTTPS', async () => {
+  const ret = await clientWith().request(targetUrl('app.example.test'))
+
+  expect(ret.body).toBe(`app.example.test:${target.address().port}`)
+  expect(doh.queries).toEqual(expect.arrayContaining([
+    { name: 'app.example.test', type: 'A', contentType: 'application/dns-message' },
+    { name: 'app.example.test', type: 'AAAA', contentType: 'application/dns-message' },
+  ]))
+})
+
+test('Caches answers for their TTL', async () => {
+  const client = clientWith()
+
+  await client.request(targetUrl('app.example.test'), { headers: { Connection: 'close' } })
+  await client.request(targetUrl('app.example.test'), { headers: { Connection: 'close' } })
+
+  expect(doh.queries.filter(q => q.type === 'A')).toHaveLength(1)
+})
+
+test('Does not cache answers with a zero TTL', async () => {
+  const client = clientWith()
+
+  await client.request(targetUrl('short.example.test'), { headers: { Connection: 'close' } })
+  await client.request(targetUrl('short.example.test'), { headers: { Connection: 'close' } })
+
+  expect(doh.queries.filter(q => q.type === 'A')).toHaveLength(2)
+})
+
+test('Fails for names without addresses', async () => {
+  const client = clientWith({ fallback: true })
+
+  await expect(client.request(targetUrl('missing.example.test'), { attempts: 0 })).rejects.toThrow()
+  await expect(client.request(targetUrl('empty.example.test'), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Uses the addresses of one family when the query for the other fails', async () => {
+  doh.failingTypes = ['AAAA']
+
+  const ret = await clientWith().request(targetUrl('app.example.test'))
+
+  expect(ret.body).toBe(`app.example.test:${target.address().port}`)
+})
+
+test('Fails when the answering family has no addresses', async () => {
+  doh.failingTypes = ['A']
+
+  await expect(clientWith().request(targetUrl('app.example.test'), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Fails when the endpoint fails without fallback', async () => {
+  doh.failing = true
+
+  await expect(clientWith().request(targetUrl('localhost'), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Falls back to the system resolver when the endpoint fails', async () => {
+  doh.failing = true
+
+  const ret = await clientWith({ fallback: true }).request(targetUrl('localhost'))
+
+  expect(ret.statusCode).toBe(200)
+})
+
+test('Rejects invalid configurations', () => {
+  expect(() => clientWith({ url: 'http://127.0.0.1/dns-query' })).toThrow('Invalid DoH URL')
+  expect(() => clientWith({ bootstrap: ['dns.example'] })).toThrow('Invalid DoH bootstrap address')
+  expect(() => new Client({
+    doh: { url: 'https://127.0.0.1/dns-query' },
+    dnsResolver: async () => [],
+  })).toThrow('Only one DNS resolver')
+})
@@ -0,0 +1,45 @@
+const path = require('path')
+const { execFile } = require('child_process')
+const { createFingerprintServer } = require('./helpers/h2-fingerprint-server')
+const { version } = require('../package.json')
+const expected = require('../fingerprints/expected.json')
+
+jest.setTimeout(10e3)
+
+let server
+let fingerprints = []
+
+beforeAll(done => {
+  server = createFingerprintServer(fp => fingerprints.push(fp))
+  server.listen(0, '127.0.0.1', done)
+})
+
+beforeEach(() => {
+  fingerprints = []
+})
+
+// The client only skips verification of the self-signed fixture through the
+// process wide NODE_TLS_REJECT_UNAUTHORIZED, which jest sandboxes, hence the child process.
+function runClient(url) {
+  return new Promise((resolve, reject) => {
+    execFile(process.execPath, [path.join(__dirname, '../fingerprints/rust-fetch.js'), url], {
+      env: { ...process.env, NODE_TLS_REJECT_UNAUTHORIZED: '0' },
+    }, (err, stdout) => (err ? reject(err) : resolve(stdout.trim())))
+  })
+}
+
+test('Expected fingerprint is recorded for the current release', () => {
+  expect(expected[version]).toBeDefined()
+})
+
+test('HTTP/2 fingerprint matches the current release', async () => {
+  const out = await runClient(`https://localhost:${server.address().port}`)
+
+  expect(out).toBe('HTTP/2.0 200')
+  expect(fingerprints).toHaveLength(1)
+  expect(fingerprints[0]).toBe(expected[version])
+})
+
+afterAll(() => {
+  server.close()
+})
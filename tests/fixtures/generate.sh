@@ -0,0 +1,28 @@
+#!/bin/sh
+# Regenerates the TLS fixtures used by the tests, run from this directory.
+set -e
+
+# Self-signed localhost certificate.
+openssl req -x509 -newkey rsa:2048 -nodes -keyout localhost.key -out localhost.crt -days 36500 \
+  -subj "/CN=localhost" -addext "subjectAltName=DNS:localhost,IP:127.0.0.1"
+
+# Self-signed certificate for a host that never matches, trusted as its own root in tests.
+openssl req -x509 -newkey rsa:2048 -nodes -keyout wrong-host.key -out wrong-host.crt -days 36500 \
+  -subj "/CN=wrong.host" -addext "subjectAltName=DNS:wrong.host"
+
+# Private CA with a server and a client certificate.
+openssl req -x509 -newkey rsa:2048 -nodes -keyout ca.key -out ca.crt -days 36500 -subj "/CN=rust-fetch test CA"
+
+openssl req -newkey rsa:2048 -nodes -keyout server.key -out server.csr -subj "/CN=localhost"
+printf "subjectAltName=DNS:localhost,IP:127.0.0.1\n" > server.ext
+openssl x509 -req -in server.csr -CA ca.crt -CAkey ca.key -CAcreateserial -out server.crt -days 36500 -extfile server.ext
+
+openssl req -newkey rsa:2048 -nodes -keyout client.key -out client.csr -subj "/CN=rust-fetch client"
+printf "extendedKeyUsage=clientAuth\n" > client.ext
+openssl x509 -req -in client.csr -CA ca.crt -CAkey ca.key -CAcreateserial -out client.crt -days 36500 -extfile client.ext
+
+# Legacy algorithms so every native-tls backend can read the bundle.
+openssl pkcs12 -export -in client.crt -inkey client.key -out client.p12 -passout pass:rust-fetch \
+  -certpbe PBE-SHA1-3DES -keypbe PBE-SHA1-3DES -macalg sha1
+
+rm -f ca.key ca.srl server.csr server.ext client.csr client.ext
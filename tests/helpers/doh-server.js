@@ -0,0 +1,116 @@
+const fs = require('fs')
+const path = require('path')
+const https = require('https')
+const net = require('net')
+
+const TYPE_A = 1
+const TYPE_AAAA = 28
+
+const TYPES = { [TYPE_A]: 'A', [TYPE_AAAA]: 'AAAA' }
+
+function parseQuestion(msg) {
+  const labels = []
+  let pos = 12
+
+  while (msg[pos] !== 0) {
+    labels.push(msg.subarray(pos + 1, pos + 1 + msg[pos]).toString())
+    pos += 1 + msg[pos]
+  }
+
+  return {
+    name: labels.join('.'),
+    type: msg.readUInt16BE(pos + 1),
+    end: pos + 5,
+  }
+}
+
+function ipv6Bytes(address) {
+  const [head, tail] = address.split('::').map(part => (part ? part.split(':') : []))
+  const groups = tail ? [...head, ...Array(8 - head.length - tail.length).fill('0'), ...tail] : head
+
+  return groups.flatMap(g => [parseInt(g, 16) >> 8, parseInt(g, 16) & 0xff])
+}
+
+function encodeAnswer(type, address, ttl) {
+  const data = Buffer.from(type === TYPE_A ? address.split('.').map(Number) : ipv6Bytes(address))
+
+  const header = Buffer.alloc(12)
+
+  // Compressed pointer to the question name.
+  header.writeUInt16BE(0xc00c, 0)
+  header.writeUInt16BE(type, 2)
+  header.writeUInt16BE(1, 4)
+  header.writeUInt32BE(ttl, 6)
+  header.writeUInt16BE(data.length, 10)
+
+  return Buffer.concat([header, data])
+}
+
+/**
+ * Answers a query from `records`, `{ name: { A: [], AAAA: [], ttl } }`, NXDOMAIN for unknown names.
+ */
+function answer(query, records) {
+  const question = parseQuestion(query)
+  const record = records[question.name]
+
+  const addresses = (record?.[TYPES[question.type]] ?? []).filter(a => net.isIP(a))
+
+  const header = Buffer.alloc(12)
+
+  header.writeUInt16BE(query.readUInt16BE(0), 0)
+  // QR, RD and RA with NOERROR or NXDOMAIN.
+  header.writeUInt16BE(0x8180 | (record ? 0 : 3), 2)
+  header.writeUInt16BE(1, 4)
+  header.writeUInt16BE(addresses.length, 6)
+
+  return Buffer.concat([
+    header,
+    query.subarray(12, question.end),
+    ...addresses.map(a => encodeAnswer(question.type, a, record.ttl ?? 60)),
+  ])
+}
+
+/**
+ * Minimal RFC 8484 DNS-over-HTTPS server for `wrong.host`, answering POST queries from
+ * `server.records` and recording them in `server.queries` as `{ name, type, contentType }`.
+ *
+ * Responds with 500 while `server.failing` is set, or to queries of the types in `server.failingTypes`.
+ */
+function createDohServer(records = {}) {
+  const server = https.createServer({
+    key: fs.readFileSync(path.join(__dirname, '../fixtures/wrong-host.key')),
+    cert: fs.readFileSync(path.join(__dirname, '../fixtures/wrong-host.crt')),
+  }, (req, res) => {
+    const chunks = []
+
+    req.on('data', chunk => chunks.push(chunk))
+    req.on('end', () => {
+      if (server.failing || req.method !== 'POST' || req.url !== '/dns-query') {
+        res.writeHead(server.failing ? 500 : 404).end()
+        return
+      }
+
+      const query = Buffer.concat(chunks)
+      const { name, type } = parseQuestion(query)
+
+      server.queries.push({ name, type: TYPES[type], contentType: req.headers['content-type'] })
+
+      if (server.failingTypes.includes(TYPES[type])) {
+        res.writeHead(500).end()
+        return
+      }
+
+      res.writeHead(200, { 'content-type': 'application/dns-message' })
+      res.end(answer(query, server.records))
+    })
+  })
+
+  server.records = records
+  server.queries = []
+  server.failing = false
+  server.failingTypes = []
+
+  return server
+}
+
+module.exports = { createDohServer }
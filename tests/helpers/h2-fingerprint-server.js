@@ -0,0 +1,146 @@
+const fs = require('fs')
+const path = require('path')
+const tls = require('tls')
+const http2 = require('http2')
+const { Duplex } = require('stream')
+
+const PREFACE = Buffer.from('PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n')
+
+const FRAME_HEADERS = 0x1
+const FRAME_SETTINGS = 0x4
+const FRAME_WINDOW_UPDATE = 0x8
+
+const FLAG_ACK = 0x1
+
+/**
+ * Parses the client side of a HTTP/2 connection up to the first HEADERS frame,
+ * recording the SETTINGS frame and connection level WINDOW_UPDATE as sent.
+ */
+class FrameRecorder {
+  constructor() {
+    this.buffer = Buffer.alloc(0)
+    this.prefaceSeen = false
+    this.done = false
+
+    this.settings = null
+    this.windowUpdate = null
+  }
+
+  push(chunk) {
+    if (this.done) return
+
+    this.buffer = Buffer.concat([this.buffer, chunk])
+
+    if (!this.prefaceSeen) {
+      if (this.buffer.length < PREFACE.length) return
+
+      this.buffer = this.buffer.subarray(PREFACE.length)
+      this.prefaceSeen = true
+    }
+
+    while (this.buffer.length >= 9) {
+      const length = this.buffer.readUIntBE(0, 3)
+
+      if (this.buffer.length < 9 + length) return
+
+      const type = this.buffer[3]
+      const flags = this.buffer[4]
+      const streamId = this.buffer.readUInt32BE(5) & 0x7fffffff
+      const payload = this.buffer.subarray(9, 9 + length)
+
+      this.buffer = this.buffer.subarray(9 + length)
+
+      if (type === FRAME_SETTINGS && !(flags & FLAG_ACK) && this.settings === null) {
+        this.settings = []
+
+        for (let i = 0; i + 6 <= payload.length; i += 6) {
+          this.settings.push([payload.readUInt16BE(i), payload.readUInt32BE(i + 2)])
+        }
+      } else if (type === FRAME_WINDOW_UPDATE && streamId === 0 && this.windowUpdate === null) {
+        this.windowUpdate = payload.readUInt32BE(0) & 0x7fffffff
+      } else if (type === FRAME_HEADERS) {
+        this.done = true
+        return
+      }
+    }
+  }
+}
+
+/**
+ * Formats a recorded connection in the Akamai HTTP/2 fingerprint format:
+ * `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`.
+ */
+function formatFingerprint({ settings, windowUpdate, pseudoHeaders }) {
+  return [
+    (settings ?? []).map(([id, value]) => `${id}:${value}`).join(','),
+    windowUpdate ?? '00',
+    '0',
+    pseudoHeaders.map(h => h[1]).join(','),
+  ].join('|')
+}
+
+/**
+ * Starts a TLS terminated HTTP/2 server on a random local port,
+ * resolving each connection's fingerprint through `onFingerprint`.
+ */
+function createFingerprintServer(onFingerprint) {
+  const h2 = http2.createServer()
+
+  h2.on('stream', (stream, _headers, _flags, rawHeaders) => {
+    const recorder = stream.session.recorder
+
+    const pseudoHeaders = rawHeaders.filter((h, i) => i % 2 === 0 && h.startsWith(':'))
+
+    onFingerprint(formatFingerprint({
+      settings: recorder.settings,
+      windowUpdate: recorder.windowUpdate,
+      pseudoHeaders,
+    }))
+
+    stream.respond({ ':status': 200 })
+    stream.end('ok')
+  })
+
+  // Sessions are created synchronously on `connection`, see below.
+  let connectingRecorder = null
+
+  h2.on('session', session => {
+    session.recorder = connectingRecorder
+  })
+
+  const server = tls.createServer({
+    key: fs.readFileSync(path.join(__dirname, '../fixtures/localhost.key')),
+    cert: fs.readFileSync(path.join(__dirname, '../fixtures/localhost.crt')),
+    ALPNProtocols: ['h2'],
+  }, socket => {
+    const recorder = new FrameRecorder()
+
+    // Tap the decrypted stream before handing it to the HTTP/2 session,
+    // node does not expose the raw SETTINGS frame otherwise.
+    const tap = new Duplex({
+      read() {},
+      write(chunk, encoding, callback) {
+        socket.write(chunk, encoding, callback)
+      },
+      final(callback) {
+        socket.end(callback)
+      },
+    })
+
+    socket.on('data', chunk => {
+      recorder.push(chunk)
+      tap.push(chunk)
+    })
+    socket.on('end', () => tap.push(null))
+    socket.on('error', err => tap.destroy(err))
+    tap.on('close', () => socket.destroy())
+
+    connectingRecorder = recorder
+    h2.emit('connection', tap)
+    connectingRecorder = null
+  })
+
+  return server
+}
+
+module.exports = { createFingerprintServer, formatFingerprint }
@@ -0,0 +1,103 @@
+const net = require('net')
+const crypto = require('crypto')
+
+const RECORD_HANDSHAKE = 0x16
+const HANDSHAKE_CLIENT_HELLO = 0x01
+
+const EXTENSION_SUPPORTED_GROUPS = 10
+const EXTENSION_EC_POINT_FORMATS = 11
+const EXTENSION_SIGNATURE_ALGORITHMS = 13
+
+// GREASE values (RFC 8701) are left out of JA3.
+const isGrease = v => (v & 0x0f0f) === 0x0a0a
+
+const uint16List = buf => {
+  const values = []
+
+  for (let i = 0; i + 1 < buf.length; i += 2) values.push(buf.readUInt16BE(i))
+
+  return values
+}
+
+/**
+ * Parses a ClientHello handshake message into the fields JA3 is made of,
+ * plus the signature algorithms which JA3 leaves out.
+ */
+function parseClientHello(msg) {
+  let pos = 4 // Handshake type and length.
+
+  const version = msg.readUInt16BE(pos)
+  pos += 2 + 32 // Random.
+
+  pos += 1 + msg[pos] // Session ID.
+
+  const ciphersLength = msg.readUInt16BE(pos)
+  const ciphers = uint16List(msg.subarray(pos + 2, pos + 2 + ciphersLength))
+  pos += 2 + ciphersLength
+
+  pos += 1 + msg[pos] // Compression methods.
+
+  const extensions = []
+  let groups = []
+  let pointFormats = []
+  let signatureAlgorithms = []
+
+  const end = pos + 2 + msg.readUInt16BE(pos)
+  pos += 2
+
+  while (pos + 4 <= end) {
+    const type = msg.readUInt16BE(pos)
+    const data = msg.subarray(pos + 4, pos + 4 + msg.readUInt16BE(pos + 2))
+    pos += 4 + data.length
+
+    extensions.push(type)
+
+    if (type === EXTENSION_SUPPORTED_GROUPS) groups = uint16List(data.subarray(2))
+    if (type === EXTENSION_EC_POINT_FORMATS) pointFormats = [...data.subarray(1)]
+    if (type === EXTENSION_SIGNATURE_ALGORITHMS) signatureAlgorithms = uint16List(data.subarray(2))
+  }
+
+  const fields = [
+    [version],
+    ciphers.filter(v => !isGrease(v)),
+    extensions.filter(v => !isGrease(v)),
+    groups.filter(v => !isGrease(v)),
+    pointFormats,
+  ]
+
+  const ja3 = fields.map(values => values.join('-')).join(',')
+
+  return {
+    ja3,
+    hash: crypto.createHash('md5').update(ja3).digest('hex'),
+    ciphers: fields[1],
+    groups: fields[3],
+    signatureAlgorithms,
+  }
+}
+
+/**
+ * TCP server reading the ClientHello of each connection, then closing it without answering.
+ */
+function createJa3Server(onClientHello) {
+  return net.createServer(socket => {
+    let buffer = Buffer.alloc(0)
+
+    socket.on('error', () => {})
+
+    socket.on('data', chunk => {
+      buffer = Buffer.concat([buffer, chunk])
+
+      // A ClientHello fits one record unless it is huge, which these are not.
+      if (buffer.length < 5 || buffer.length < 5 + buffer.readUInt16BE(3)) return
+
+      if (buffer[0] === RECORD_HANDSHAKE && buffer[5] === HANDSHAKE_CLIENT_HELLO) {
+        onClientHello(parseClientHello(buffer.subarray(5, 5 + buffer.readUInt16BE(3))))
+      }
+
+      socket.destroy()
+    })
+  })
+}
+
+module.exports = { createJa3Server, parseClientHello }
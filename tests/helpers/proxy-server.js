@@ -0,0 +1,42 @@
+const net = require('net')
+const http = require('http')
+
+/**
+ * Minimal forwarding proxy recording each request it handles in `server.seen`,
+ * `{ method, target, headers }`, CONNECT tunnels included.
+ */
+function createProxyServer(name = 'proxy') {
+  const server = http.createServer((req, res) => {
+    server.seen.push({ method: req.method, target: req.url, headers: req.headers })
+
+    const upstream = http.request(req.url, { method: req.method, headers: req.headers }, upstreamRes => {
+      res.writeHead(upstreamRes.statusCode, { ...upstreamRes.headers, 'x-served-by': name })
+      upstreamRes.pipe(res)
+    })
+
+    upstream.on('error', () => res.writeHead(502).end())
+    req.pipe(upstream)
+  })
+
+  server.on('connect', (req, socket, head) => {
+    server.seen.push({ method: req.method, target: req.url, headers: req.headers })
+
+    const [host, port] = req.url.split(':')
+
+    const upstream = net.connect(Number(port), host, () => {
+      socket.write('HTTP/1.1 200 Connection Established\r\n\r\n')
+      upstream.write(head)
+      upstream.pipe(socket)
+      socket.pipe(upstream)
+    })
+
+    upstream.on('error', () => socket.destroy())
+    socket.on('error', () => upstream.destroy())
+  })
+
+  server.seen = []
+
+  return server
+}
+
+module.exports = { createProxyServer }
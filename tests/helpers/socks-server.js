@@ -0,0 +1,179 @@
+const net = require('net')
+
+const ATYP_IPV4 = 0x01
+const ATYP_DOMAIN = 0x03
+const ATYP_IPV6 = 0x04
+
+const REPLY_OK = Buffer.from([0x05, 0x00, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+const REPLY4_GRANTED = Buffer.from([0x00, 0x5a, 0, 0, 0, 0, 0, 0])
+const REPLY4_REJECTED = Buffer.from([0x00, 0x5b, 0, 0, 0, 0, 0, 0])
+
+/**
+ * Reads exactly `n` bytes from a paused socket.
+ */
+function read(socket, n) {
+  return new Promise((resolve, reject) => {
+    const tryRead = () => {
+      const chunk = socket.read(n)
+
+      if (chunk === null) return socket.once('readable', tryRead)
+
+      socket.off('close', onClose)
+      resolve(chunk)
+    }
+
+    const onClose = () => reject(new Error('Socket closed'))
+
+    socket.once('close', onClose)
+    tryRead()
+  })
+}
+
+/**
+ * Reads a null terminated string.
+ */
+async function readString(socket) {
+  const bytes = []
+
+  for (;;) {
+    const [byte] = await read(socket, 1)
+
+    if (byte === 0) return Buffer.from(bytes).toString()
+
+    bytes.push(byte)
+  }
+}
+
+function ipv6(buffer) {
+  const groups = []
+
+  for (let i = 0; i < 16; i += 2) groups.push(buffer.readUInt16BE(i).toString(16))
+
+  return groups.join(':')
+}
+
+function connect(socket, host, port, reply) {
+  const upstream = net.connect(port, host, () => {
+    socket.write(reply)
+    upstream.pipe(socket)
+    socket.pipe(upstream)
+    socket.resume()
+  })
+
+  upstream.on('error', () => socket.destroy())
+  socket.on('error', () => upstream.destroy())
+}
+
+async function handshake4(server, socket, command) {
+  const header = await read(socket, 6)
+  const port = header.readUInt16BE(0)
+  const ip = [...header.subarray(2)]
+  const userId = await readString(socket)
+
+  // SOCKS4a: the invalid address 0.0.0.x is followed by the hostname.
+  const isDomain = ip[0] === 0 && ip[1] === 0 && ip[2] === 0 && ip[3] !== 0
+  const host = isDomain ? await readString(socket) : ip.join('.')
+
+  server.seen.push({ version: 4, type: isDomain ? 'domain' : 'ipv4', host, port, username: userId || null })
+
+  if (command !== 0x01) {
+    socket.end(REPLY4_REJECTED)
+    return
+  }
+
+  connect(socket, host, port, REPLY4_GRANTED)
+}
+
+async function handshake(server, socket) {
+  const [version, methodCount] = await read(socket, 2)
+
+  if (version === 0x04) {
+    await handshake4(server, socket, methodCount)
+    return
+  }
+
+  if (version !== 0x05) throw new Error(`Unsupported SOCKS version ${version}`)
+
+  const methods = [...await read(socket, methodCount)]
+  const method = server.credentials ? 0x02 : 0x00
+
+  if (!methods.includes(method)) {
+    socket.end(Buffer.from([0x05, 0xff]))
+    return
+  }
+
+  socket.write(Buffer.from([0x05, method]))
+
+  let username = null
+
+  // RFC 1929 username/password authentication.
+  if (server.credentials) {
+    const [, userLength] = await read(socket, 2)
+    username = (await read(socket, userLength)).toString()
+    const [passLength] = await read(socket, 1)
+    const password = (await read(socket, passLength)).toString()
+
+    const valid = username === server.credentials.username && password === server.credentials.password
+
+    socket.write(Buffer.from([0x01, valid ? 0x00 : 0x01]))
+
+    if (!valid) {
+      socket.end()
+      return
+    }
+  }
+
+  const [, command, , atyp] = await read(socket, 4)
+
+  let host
+
+  if (atyp === ATYP_IPV4) {
+    host = [...await read(socket, 4)].join('.')
+  } else if (atyp === ATYP_DOMAIN) {
+    const [length] = await read(socket, 1)
+    host = (await read(socket, length)).toString()
+  } else if (atyp === ATYP_IPV6) {
+    host = ipv6(await read(socket, 16))
+  }
+
+  const port = (await read(socket, 2)).readUInt16BE(0)
+
+  server.seen.push({
+    version: 5,
+    type: { [ATYP_IPV4]: 'ipv4', [ATYP_DOMAIN]: 'domain', [ATYP_IPV6]: 'ipv6' }[atyp],
+    host,
+    port,
+    username,
+  })
+
+  // Only CONNECT is supported.
+  if (command !== 0x01) {
+    socket.end(Buffer.from([0x05, 0x07, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0]))
+    return
+  }
+
+  connect(socket, host, port, REPLY_OK)
+}
+
+/**
+ * Minimal SOCKS5 and SOCKS4(a) proxy supporting CONNECT, recording each request in `server.seen` as
+ * `{ version, type, host, port, username }`, `type` being how the destination was addressed.
+ *
+ * Requires SOCKS5 username/password authentication when `credentials` are given,
+ * SOCKS4 user IDs are recorded as `username`.
+ */
+function createSocksServer(credentials = null) {
+  const server = net.createServer(socket => {
+    socket.pause()
+    socket.on('error', () => {})
+
+    handshake(server, socket).catch(() => socket.destroy())
+  })
+
+  server.credentials = credentials
+  server.seen = []
+
+  return server
+}
+
+module.exports = { createSocksServer }
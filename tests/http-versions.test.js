@@ -0,0 +1,98 @@
+const fs = require('fs')
+const path = require('path')
+const http2 = require('http2')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+const fixture = name => fs.readFileSync(path.join(__dirname, 'fixtures', name))
+
+let secure
+let cleartext
+
+function listen(server) {
+  return new Promise(resolve => server.listen(0, '127.0.0.1', () => resolve(server)))
+}
+
+const handler = (req, res) => res.end(req.httpVersion)
+
+const clientWith = options => new Client({
+  connectTimeout: 5,
+  requestTimeout: 5,
+  rootCertificates: [fixture('ca.crt')],
+  ...options,
+})
+
+const secureUrl = () => `https://localhost:${secure.address().port}`
+
+beforeAll(async () => {
+  secure = await listen(http2.createSecureServer({
+    key: fixture('server.key'),
+    cert: fixture('server.crt'),
+    allowHTTP1: true,
+    minVersion: 'TLSv1.2',
+    maxVersion: 'TLSv1.2',
+  }, handler))
+
+  cleartext = await listen(http2.createServer(handler))
+})
+
+test('HTTP/2 is negotiated by default', async () => {
+  const ret = await clientWith({}).request(secureUrl())
+
+  expect(ret.httpVersion).toBe('HTTP/2.0')
+})
+
+test('HTTP/1.1 only', async () => {
+  const ret = await clientWith({ http1Only: true }).request(secureUrl())
+
+  expect(ret.httpVersion).toBe('HTTP/1.1')
+  expect(ret.body).toBe('1.1')
+})
+
+test('ALPN http/1.1 only', async () => {
+  const ret = await clientWith({ alpnProtocols: ['http/1.1'] }).request(secureUrl())
+
+  expect(ret.httpVersion).toBe('HTTP/1.1')
+})
+
+test('HTTP/2 prior knowledge over cleartext', async () => {
+  const ret = await clientWith({ http2PriorKnowledge: true }).request(`http://127.0.0.1:${cleartext.address().port}`)
+
+  expect(ret.httpVersion).toBe('HTTP/2.0')
+  expect(ret.body).toBe('2.0')
+})
+
+test('Conflicting HTTP versions should throw', () => {
+  expect(() => clientWith({ http1Only: true, http2PriorKnowledge: true })).toThrow('Conflicting HTTP version options')
+  expect(() => clientWith({ alpnProtocols: ['spdy/3'] })).toThrow('Unsupported ALPN protocols')
+})
+
+test('Minimum TLS version within server bounds', async () => {
+  const ret = await clientWith({ minTlsVersion: '1.2' }).request(secureUrl())
+
+  expect(ret.statusCode).toBe(200)
+})
+
+test('Maximum TLS version below server minimum should error', async () => {
+  const strict = await listen(http2.createSecureServer({
+    key: fixture('server.key'),
+    cert: fixture('server.crt'),
+    minVersion: 'TLSv1.3',
+  }, handler))
+
+  const client = clientWith({ maxTlsVersion: '1.2' })
+
+  await expect(client.request(`https://localhost:${strict.address().port}`, { attempts: 0 })).rejects.toThrow()
+
+  strict.close()
+})
+
+test('Invalid TLS version should throw', () => {
+  expect(() => clientWith({ minTlsVersion: '2.0' })).toThrow('Invalid TLS version')
+})
+
+afterAll(() => {
+  secure.close()
+  cleartext.close()
+})
@@ -0,0 +1,66 @@
+const { Client } = require('../dist')
+const { createFingerprintServer } = require('./helpers/h2-fingerprint-server')
+
+jest.setTimeout(10e3)
+
+let server
+let fingerprints = []
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, acceptInvalidCerts: true, ...options })
+
+function parse(fingerprint) {
+  const [settings, windowUpdate] = fingerprint.split('|')
+
+  return {
+    settings: Object.fromEntries(settings.split(',').map(s => s.split(':').map(Number))),
+    windowUpdate: Number(windowUpdate),
+  }
+}
+
+beforeAll(done => {
+  server = createFingerprintServer(fp => fingerprints.push(fp))
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+beforeEach(() => {
+  fingerprints = []
+})
+
+test('Sends the configured windows and frame size', async () => {
+  const client = clientWith({
+    http2InitialStreamWindowSize: 65535,
+    http2InitialConnectionWindowSize: 15728640,
+    http2MaxFrameSize: 32768,
+  })
+
+  const ret = await client.request(`https://localhost:${server.address().port}/`)
+
+  expect(ret.httpVersion).toBe('HTTP/2.0')
+
+  const { settings, windowUpdate } = parse(fingerprints[0])
+
+  expect(settings[4]).toBe(65535)
+  expect(settings[5]).toBe(32768)
+  expect(windowUpdate).toBe(15728640 - 65535)
+})
+
+test('Keeps the default stream window', async () => {
+  await clientWith().request(`https://localhost:${server.address().port}/`)
+
+  const { settings } = parse(fingerprints[0])
+
+  expect(settings[4]).toBe(6291456)
+  expect(settings[5]).toBeUndefined()
+})
+
+test('Validates settings when built', () => {
+  expect(() => clientWith({ http2InitialStreamWindowSize: 0 })).toThrow('Invalid HTTP/2 initial stream window size')
+  expect(() => clientWith({ http2InitialConnectionWindowSize: 2 ** 31 })).toThrow('Invalid HTTP/2 initial connection window size')
+  expect(() => clientWith({ http2MaxFrameSize: 1024 })).toThrow('Invalid HTTP/2 max frame size')
+  expect(() => clientWith({ http2MaxFrameSize: 16384.5 })).toThrow('expected an integer')
+  expect(() => clientWith({ https2AdaptiveWindow: true, http2InitialStreamWindowSize: 65535 })).toThrow('adaptive window')
+})
@@ -0,0 +1,74 @@
+const fs = require('fs')
+const path = require('path')
+const https = require('https')
+const { Client, tlsBackend } = require('../dist')
+
+jest.setTimeout(10e3)
+
+const nativeTlsTest = tlsBackend === 'native-tls' ? test : test.skip
+
+const fixture = name => fs.readFileSync(path.join(__dirname, 'fixtures', name))
+
+let selfSigned
+let wrongHost
+
+function listen(key, cert) {
+  return new Promise(resolve => {
+    const server = https.createServer({ key: fixture(key), cert: fixture(cert) }, (_req, res) => {
+      res.end('ok')
+    })
+
+    server.listen(0, '127.0.0.1', () => resolve(server))
+  })
+}
+
+const urlOf = server => `https://localhost:${server.address().port}`
+
+beforeAll(async () => {
+  selfSigned = await listen('localhost.key', 'localhost.crt')
+  wrongHost = await listen('wrong-host.key', 'wrong-host.crt')
+})
+
+test('Self-signed certificate should error by default', async () => {
+  const client = new Client({ connectTimeout: 5, requestTimeout: 5 })
+
+  await expect(client.request(urlOf(selfSigned), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Accept invalid certs for a single client', async () => {
+  const insecure = new Client({ connectTimeout: 5, requestTimeout: 5, acceptInvalidCerts: true })
+  const strict = new Client({ connectTimeout: 5, requestTimeout: 5 })
+
+  const ret = await insecure.request(urlOf(selfSigned))
+
+  expect(ret.statusCode).toBe(200)
+  await expect(strict.request(urlOf(selfSigned), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Hostname mismatch should error by default', async () => {
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    rootCertificates: [fixture('wrong-host.crt')],
+  })
+
+  await expect(client.request(urlOf(wrongHost), { attempts: 0 })).rejects.toThrow()
+})
+
+nativeTlsTest('Accept invalid hostnames', async () => {
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    rootCertificates: [fixture('wrong-host.crt')],
+    acceptInvalidHostnames: true,
+  })
+
+  const ret = await client.request(urlOf(wrongHost))
+
+  expect(ret.statusCode).toBe(200)
+})
+
+afterAll(() => {
+  selfSigned.close()
+  wrongHost.close()
+})
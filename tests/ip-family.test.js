@@ -0,0 +1,69 @@
+const os = require('os')
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let target
+
+const linuxTest = os.platform() === 'linux' ? test : test.skip
+
+const targetUrl = host => `http://${host}:${target.address().port}/`
+
+const clientWith = options => new Client({
+  connectTimeout: 5,
+  requestTimeout: 5,
+  dnsResolver: async () => [{ address: '::1' }, { address: '127.0.0.1' }],
+  ...options,
+})
+
+beforeAll(async () => {
+  target = await new Promise(resolve => {
+    const server = http.createServer((req, res) => res.end(req.socket.remoteAddress))
+
+    server.listen(0, '127.0.0.1', () => resolve(server))
+  })
+})
+
+afterAll(() => {
+  target.close()
+})
+
+test('v4 only connects to IPv4 addresses', async () => {
+  const ret = await clientWith({ ipFamily: 'v4' }).request(targetUrl('dual.example.test'))
+
+  expect(ret.body).toBe('127.0.0.1')
+})
+
+test('v6 only fails without IPv6 addresses', async () => {
+  const client = clientWith({ ipFamily: 'v6', dnsResolver: async () => [{ address: '127.0.0.1' }] })
+
+  await expect(client.request(targetUrl('v4.example.test'), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Falls back to the other family without a family', async () => {
+  const ret = await clientWith().request(targetUrl('dual.example.test'))
+
+  expect(ret.body).toBe('127.0.0.1')
+})
+
+// The whole 127.0.0.0/8 network is local on Linux.
+linuxTest('Binds to the local address', async () => {
+  const ret = await clientWith({ localAddress: '127.0.0.2' }).request(targetUrl('127.0.0.1'))
+
+  expect(ret.body).toBe('127.0.0.2')
+})
+
+linuxTest('Binds to the address of a network interface', async () => {
+  const ret = await clientWith({ networkInterface: 'lo', ipFamily: 'v4' }).request(targetUrl('127.0.0.1'))
+
+  expect(ret.body).toBe('127.0.0.1')
+})
+
+test('Rejects invalid options', () => {
+  expect(() => clientWith({ ipFamily: 'v5' })).toThrow('Invalid IP family')
+  expect(() => clientWith({ localAddress: 'localhost' })).toThrow('Invalid local address')
+  expect(() => clientWith({ localAddress: '::1', ipFamily: 'v4' })).toThrow('does not match the IP family')
+  expect(() => clientWith({ networkInterface: 'does-not-exist0' })).toThrow('has no IPv4 address')
+  expect(() => clientWith({ localAddress: '127.0.0.1', networkInterface: 'lo' })).toThrow('cannot be combined')
+})
@@ -0,0 +1,131 @@
+const fs = require('fs')
+const os = require('os')
+const path = require('path')
+const dns = require('dns')
+const http = require('http')
+const { Client } = require('../dist')
+const { parsePacResult } = require('../dist/pac')
+const { createProxyServer } = require('./helpers/proxy-server')
+
+jest.setTimeout(10e3)
+
+let proxy
+let target
+let redirect
+
+function listen(server) {
+  return new Promise(resolve => server.listen(0, '127.0.0.1', () => resolve(server)))
+}
+
+const pacSource = () => fs
+  .readFileSync(path.join(__dirname, 'fixtures/proxy.pac'), 'utf8')
+  .replace('PROXY_PORT', proxy.address().port)
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+beforeAll(async () => {
+  proxy = await listen(createProxyServer())
+  target = await listen(http.createServer((_req, res) => res.end('target')))
+  redirect = await listen(http.createServer((_req, res) => {
+    res.writeHead(302, { location: `http://127.0.0.1:${target.address().port}/` })
+    res.end()
+  }))
+})
+
+afterAll(() => {
+  proxy.close()
+  target.close()
+  redirect.close()
+})
+
+beforeEach(() => {
+  proxy.seen = []
+})
+
+afterEach(() => {
+  jest.restoreAllMocks()
+})
+
+test('Proxies hosts the script sends to a proxy', async () => {
+  const ret = await clientWith({ pac: { source: pacSource() } }).request(`http://127.0.0.1:${target.address().port}/`)
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`http://127.0.0.1:${proxy.address().port}/`)
+  expect(proxy.seen).toHaveLength(1)
+})
+
+test('Connects directly for DIRECT hosts', async () => {
+  const ret = await clientWith({ pac: { source: pacSource() } }).request(`http://localhost:${target.address().port}/`)
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBeNull()
+  expect(proxy.seen).toHaveLength(0)
+})
+
+test('Evaluates the script for redirect targets', async () => {
+  const ret = await clientWith({ pac: { source: pacSource() } }).request(`http://localhost:${redirect.address().port}/`)
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`http://127.0.0.1:${proxy.address().port}/`)
+  expect(proxy.seen).toEqual([expect.objectContaining({ target: `http://127.0.0.1:${target.address().port}/` })])
+})
+
+test('Does not follow redirects to hosts without a result for other methods', async () => {
+  const client = clientWith({ pac: { source: pacSource() } })
+
+  await expect(client.request(`http://localhost:${redirect.address().port}/`, { method: 'POST', body: 'x' }))
+    .rejects.toMatchObject({ code: 'ERR_PAC_UNRESOLVED', url: `http://127.0.0.1:${target.address().port}/` })
+  expect(proxy.seen).toHaveLength(0)
+})
+
+test('Only looks up hosts the script resolves', async () => {
+  const lookup = jest.spyOn(dns.promises, 'lookup')
+
+  await clientWith({ pac: { source: pacSource() } }).request(`http://localhost:${target.address().port}/`)
+
+  expect(lookup).not.toHaveBeenCalled()
+
+  const source = `function FindProxyForURL(url, host) {
+    return isInNet(dnsResolve(host), '127.0.0.0', '255.0.0.0') ? 'PROXY 127.0.0.1:${proxy.address().port}' : 'DIRECT'
+  }`
+
+  const ret = await clientWith({ pac: { source } }).request(`http://localhost:${target.address().port}/`)
+
+  expect(lookup).toHaveBeenCalledTimes(1)
+  expect(lookup).toHaveBeenCalledWith('localhost', { family: 4 })
+  expect(ret.proxy).toBe(`http://127.0.0.1:${proxy.address().port}/`)
+})
+
+test('Reads the script from a file', async () => {
+  const file = path.join(fs.mkdtempSync(path.join(os.tmpdir(), 'rust-fetch-')), 'proxy.pac')
+
+  fs.writeFileSync(file, pacSource())
+
+  const ret = await clientWith({ pac: { path: file } }).request(`http://127.0.0.1:${target.address().port}/`)
+
+  expect(ret.proxy).toBe(`http://127.0.0.1:${proxy.address().port}/`)
+  expect(proxy.seen).toHaveLength(1)
+})
+
+test('Rejects scripts without FindProxyForURL', () => {
+  expect(() => clientWith({ pac: { source: 'var x = 1' } })).toThrow('FindProxyForURL')
+})
+
+test('Bounds each FindProxyForURL call by the script timeout', async () => {
+  const client = clientWith({ pac: { source: 'function FindProxyForURL(url, host) { for (;;) {} }' } })
+
+  await expect(client.request(`http://127.0.0.1:${target.address().port}/`))
+    .rejects.toMatchObject({ code: 'ERR_SCRIPT_EXECUTION_TIMEOUT' })
+})
+
+test('Cannot be combined with proxy', () => {
+  expect(() => clientWith({ pac: { source: pacSource() }, proxy: 'http://127.0.0.1:1' })).toThrow('PAC script')
+})
+
+test('Uses the first supported entry', () => {
+  expect(parsePacResult('SOCKS4 a:1; SOCKS b:2')).toBe('socks4://a:1')
+  expect(parsePacResult('SOCKS b:2')).toBe('socks5://b:2')
+  expect(parsePacResult('HTTPS a:443; DIRECT')).toBe('https://a:443')
+  expect(parsePacResult('QUIC a:443; PROXY b:8080')).toBe('http://b:8080')
+  expect(parsePacResult('QUIC a:443')).toBe('')
+})
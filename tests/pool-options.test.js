@@ -0,0 +1,100 @@
+const fs = require('fs')
+const path = require('path')
+const http = require('http')
+const http2 = require('http2')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+let connections
+let h2
+let pings
+
+const sleep = ms => new Promise(resolve => setTimeout(resolve, ms))
+
+const serverUrl = () => `http://127.0.0.1:${server.address().port}/`
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+beforeAll(async () => {
+  server = http.createServer((_req, res) => res.end('ok'))
+  server.on('connection', () => connections++)
+
+  h2 = http2.createSecureServer({
+    key: fs.readFileSync(path.join(__dirname, 'fixtures/localhost.key')),
+    cert: fs.readFileSync(path.join(__dirname, 'fixtures/localhost.crt')),
+  }, (_req, res) => res.end('ok'))
+  h2.on('session', session => session.on('ping', () => pings++))
+
+  await Promise.all([server, h2].map(s => new Promise(resolve => s.listen(0, '127.0.0.1', resolve))))
+})
+
+afterAll(() => {
+  server.close()
+  h2.close()
+})
+
+beforeEach(() => {
+  connections = 0
+  pings = 0
+})
+
+test('Reuses idle connections by default', async () => {
+  const client = clientWith()
+
+  await client.request(serverUrl())
+  await client.request(serverUrl())
+
+  expect(connections).toBe(1)
+})
+
+test('poolMaxIdlePerHost 0 disables reuse', async () => {
+  const client = clientWith({ poolMaxIdlePerHost: 0 })
+
+  await client.request(serverUrl())
+  await client.request(serverUrl())
+
+  expect(connections).toBe(2)
+})
+
+test('poolIdleTimeout closes idle connections', async () => {
+  const client = clientWith({ poolIdleTimeout: 0.2 })
+
+  await client.request(serverUrl())
+  await sleep(500)
+  await client.request(serverUrl())
+
+  expect(connections).toBe(2)
+})
+
+test('Accepts TCP socket options', async () => {
+  const client = clientWith({ tcpKeepalive: 30, tcpNodelay: false, poolIdleTimeout: null })
+
+  const ret = await client.request(serverUrl())
+
+  expect(ret.body).toBe('ok')
+})
+
+test('Sends HTTP/2 keep-alive pings while idle', async () => {
+  const client = clientWith({
+    acceptInvalidCerts: true,
+    http2KeepAliveInterval: 0.2,
+    http2KeepAliveTimeout: 1,
+    http2KeepAliveWhileIdle: true,
+  })
+
+  const ret = await client.request(`https://localhost:${h2.address().port}/`)
+
+  expect(ret.httpVersion).toBe('HTTP/2.0')
+
+  await sleep(700)
+
+  expect(pings).toBeGreaterThan(0)
+})
+
+test('Rejects invalid values', () => {
+  expect(() => clientWith({ poolMaxIdlePerHost: -1 })).toThrow('Invalid pool max idle per host')
+  expect(() => clientWith({ poolIdleTimeout: -1 })).toThrow('Invalid pool idle timeout')
+  expect(() => clientWith({ http2KeepAliveTimeout: null })).toThrow('HTTP/2 keep-alive timeout is required')
+})
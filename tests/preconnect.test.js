@@ -0,0 +1,156 @@
+const fs = require('fs')
+const net = require('net')
+const path = require('path')
+const http2 = require('http2')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+let sessions
+let requests
+
+const serverOrigin = () => `https://localhost:${server.address().port}`
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, acceptInvalidCerts: true, ...options })
+
+beforeAll(done => {
+  server = http2.createSecureServer({
+    key: fs.readFileSync(path.join(__dirname, 'fixtures/localhost.key')),
+    cert: fs.readFileSync(path.join(__dirname, 'fixtures/localhost.crt')),
+    allowHTTP1: true,
+  }, (req, res) => {
+    requests.push(`${req.method} ${req.url}`)
+
+    if (req.url === '/') {
+      res.setHeader('location', '/elsewhere')
+      res.setHeader('set-cookie', 'preconnect=1')
+      res.statusCode = 302
+    }
+
+    if (req.url === '/slow') {
+      setTimeout(() => res.end('ok'), 300)
+    } else {
+      res.end('ok')
+    }
+  })
+  server.on('session', () => sessions++)
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+beforeEach(() => {
+  sessions = 0
+  requests = []
+})
+
+test('Reuses the preconnected connection', async () => {
+  const client = clientWith()
+
+  const ret = await client.preconnect(`${serverOrigin()}/some/path?q=1`)
+
+  expect(ret.origin).toBe(serverOrigin())
+  expect(ret.httpVersion).toBe('HTTP/2.0')
+  expect(ret.duration).toBeGreaterThanOrEqual(0)
+
+  await client.request(`${serverOrigin()}/api`)
+
+  expect(sessions).toBe(1)
+  expect(requests).toEqual(['HEAD /', 'GET /api'])
+})
+
+test('Follows no redirects and stores no cookies', async () => {
+  const client = clientWith()
+
+  // Cookies stored while a request is in flight would be reported as its new cookies.
+  const pending = client.request(`${serverOrigin()}/slow`)
+
+  await new Promise(resolve => server.once('request', resolve))
+
+  await client.preconnect(serverOrigin())
+
+  const res = await pending
+
+  expect(requests).toEqual(['GET /slow', 'HEAD /'])
+  expect(res.newCookies).toEqual({})
+})
+
+test('Is not held back by the concurrency and rate limits', async () => {
+  const client = clientWith({
+    maxConcurrentRequests: 1,
+    rateLimits: [{ origin: '*', requests: 1, interval: 10 }],
+  })
+
+  await client.preconnect(serverOrigin())
+
+  const start = Date.now()
+
+  await client.request(`${serverOrigin()}/api`)
+
+  expect(Date.now() - start).toBeLessThan(1000)
+  expect(client.stats().inFlight).toBe(0)
+})
+
+test('Makes the first request faster', async () => {
+  // Takes 300ms to accept each connection.
+  const slow = net.createServer(socket => {
+    socket.pause()
+
+    setTimeout(() => {
+      const upstream = net.connect(server.address().port, '127.0.0.1')
+
+      socket.on('error', () => upstream.destroy())
+      upstream.on('error', () => socket.destroy())
+      socket.pipe(upstream).pipe(socket)
+      socket.resume()
+    }, 300)
+  })
+
+  await new Promise(resolve => slow.listen(0, '127.0.0.1', resolve))
+
+  const slowUrl = `https://localhost:${slow.address().port}/api`
+
+  const timed = async client => {
+    const start = Date.now()
+
+    await client.request(slowUrl)
+
+    return Date.now() - start
+  }
+
+  const cold = await timed(clientWith())
+
+  const warm = clientWith()
+
+  await warm.preconnect(slowUrl)
+
+  const warmed = await timed(warm)
+
+  expect(cold).toBeGreaterThanOrEqual(300)
+  expect(warmed).toBeLessThan(300)
+
+  slow.close()
+})
+
+test('Reports the protocol negotiated', async () => {
+  const client = clientWith({ http1Only: true })
+
+  const ret = await client.preconnect(serverOrigin())
+
+  expect(ret.httpVersion).toBe('HTTP/1.1')
+})
+
+test('Rejects unreachable origins', async () => {
+  const client = clientWith()
+
+  await expect(client.preconnect('https://127.0.0.1:1')).rejects.toThrow()
+})
+
+test('Rejects non HTTP origins', async () => {
+  const client = clientWith()
+
+  await expect(client.preconnect('ftp://localhost')).rejects.toThrow('Invalid origin')
+})
@@ -0,0 +1,135 @@
+const net = require('net')
+const http = require('http')
+const { Client } = require('../dist')
+const { createProxyServer } = require('./helpers/proxy-server')
+
+jest.setTimeout(10e3)
+
+let proxies
+let target
+let deadPort
+
+function listen(server) {
+  return new Promise(resolve => server.listen(0, '127.0.0.1', () => resolve(server)))
+}
+
+const proxyUrl = server => `http://127.0.0.1:${server.address().port}/`
+const targetUrl = () => `http://127.0.0.1:${target.address().port}/`
+
+const clientWith = proxyPool => new Client({ connectTimeout: 5, requestTimeout: 5, proxyPool })
+
+beforeAll(async () => {
+  proxies = await Promise.all(['a', 'b', 'c'].map(name => listen(createProxyServer(name))))
+  target = await listen(http.createServer((_req, res) => res.end('target')))
+
+  // A port nothing listens on, for connect errors.
+  const closed = await listen(net.createServer())
+  deadPort = closed.address().port
+  closed.close()
+})
+
+afterAll(() => {
+  proxies.forEach(proxy => proxy.close())
+  target.close()
+})
+
+async function servedBy(client, count) {
+  const served = []
+
+  for (let i = 0; i < count; i++) {
+    const ret = await client.request(targetUrl())
+
+    expect(ret.body).toBe('target')
+    expect(ret.proxy).toBe(proxyUrl(proxies.find(p => p.name === ret.headers['x-served-by'])))
+
+    served.push(ret.headers['x-served-by'])
+  }
+
+  return served
+}
+
+beforeEach(() => {
+  for (const [i, proxy] of proxies.entries()) {
+    proxy.name = ['a', 'b', 'c'][i]
+    proxy.seen = []
+  }
+})
+
+test('Round robin uses every proxy in turn', async () => {
+  const client = clientWith({ proxies: proxies.map(proxyUrl) })
+
+  expect(await servedBy(client, 6)).toEqual(['a', 'b', 'c', 'a', 'b', 'c'])
+})
+
+test('Sticky keeps an origin on one proxy', async () => {
+  const client = clientWith({ proxies: proxies.map(proxyUrl), rotation: 'sticky' })
+
+  const served = await servedBy(client, 4)
+
+  expect(new Set(served).size).toBe(1)
+})
+
+test('Sticky keeps an origin on a proxy that failed for another origin', async () => {
+  // Plain TCP forwarder, whose listener can be closed while keeping pooled connections open.
+  const forwarder = await listen(net.createServer(socket => {
+    const upstream = net.connect(proxies[2].address().port, '127.0.0.1')
+
+    socket.on('error', () => upstream.destroy())
+    upstream.on('error', () => socket.destroy())
+    socket.pipe(upstream).pipe(socket)
+  }))
+  const forwarderUrl = proxyUrl(forwarder)
+  const targets = await Promise.all([0, 1].map(() => listen(http.createServer((_req, res) => res.end('target')))))
+  const client = clientWith({ proxies: [forwarderUrl, proxyUrl(proxies[1])], rotation: 'sticky' })
+
+  const first = await client.request(targetUrl())
+  const second = await client.request(`http://127.0.0.1:${targets[0].address().port}/`)
+
+  expect([first.headers['x-served-by'], second.headers['x-served-by']]).toEqual(['c', 'b'])
+
+  forwarder.close()
+
+  // Fails over to b, while the first origin still has an idle connection through c.
+  const third = await client.request(`http://127.0.0.1:${targets[1].address().port}/`)
+  const fourth = await client.request(targetUrl())
+
+  expect(third.headers['x-served-by']).toBe('b')
+  expect(fourth.headers['x-served-by']).toBe('c')
+  expect(fourth.proxy).toBe(forwarderUrl)
+
+  targets.forEach(server => server.close())
+})
+
+test('Random only uses proxies of the pool', async () => {
+  const client = clientWith({ proxies: proxies.map(proxyUrl), rotation: 'random' })
+
+  for (const name of await servedBy(client, 6)) {
+    expect(['a', 'b', 'c']).toContain(name)
+  }
+})
+
+test('Fails over when a proxy cannot be connected to', async () => {
+  const client = clientWith({ proxies: [`http://127.0.0.1:${deadPort}`, proxyUrl(proxies[0])] })
+
+  // The first request fails over within its attempts, later ones skip the failed proxy.
+  expect(await servedBy(client, 3)).toEqual(['a', 'a', 'a'])
+})
+
+test('Retries a failed proxy after its cooldown', async () => {
+  const client = clientWith({ proxies: [`http://127.0.0.1:${deadPort}`, proxyUrl(proxies[0])], failoverCooldown: 0 })
+
+  await servedBy(client, 2)
+
+  await expect(client.request(targetUrl(), { attempts: 0 })).rejects.toThrow()
+})
+
+test('Rejects invalid pools', () => {
+  expect(() => clientWith({ proxies: [] })).toThrow('at least one proxy')
+  expect(() => clientWith({ proxies: ['ftp://127.0.0.1:1'] })).toThrow('Unsupported proxy scheme')
+  expect(() => clientWith({ proxies: [proxyUrl(proxies[0])], rotation: 'weighted' })).toThrow('Invalid proxy rotation')
+  expect(() => clientWith({ proxies: [proxyUrl(proxies[0])], failoverCooldown: -1 })).toThrow('Invalid proxy pool failover cooldown')
+  expect(() => clientWith({ proxies: [proxyUrl(proxies[0])], failoverCooldown: 1e300 })).toThrow('Invalid proxy pool failover cooldown')
+  expect(() => new Client({ proxy: proxyUrl(proxies[0]), proxyPool: { proxies: [proxyUrl(proxies[0])] } })).toThrow('proxy pool')
+  expect(() => new Client({ poolMaxIdlePerHost: 4, proxyPool: { proxies: [proxyUrl(proxies[0])] } })).toThrow('poolMaxIdlePerHost')
+  expect(() => new Client({ poolMaxIdlePerHost: 4, proxyPool: { proxies: [proxyUrl(proxies[0])], rotation: 'sticky' } })).not.toThrow()
+})
@@ -0,0 +1,163 @@
+const fs = require('fs')
+const path = require('path')
+const http = require('http')
+const https = require('https')
+const { Client } = require('../dist')
+const { createProxyServer } = require('./helpers/proxy-server')
+
+jest.setTimeout(10e3)
+
+const fixture = name => fs.readFileSync(path.join(__dirname, 'fixtures', name))
+
+let proxy
+let target
+let secureTarget
+
+function listen(server) {
+  return new Promise(resolve => server.listen(0, '127.0.0.1', () => resolve(server)))
+}
+
+const portOf = server => server.address().port
+
+const clientWith = proxyOptions => new Client({
+  connectTimeout: 5,
+  requestTimeout: 5,
+  rootCertificates: [fixture('ca.crt')],
+  proxy: proxyOptions,
+})
+
+beforeAll(async () => {
+  proxy = await listen(createProxyServer())
+  target = await listen(http.createServer((req, res) => {
+    if (req.url === '/headers') {
+      res.end(JSON.stringify(req.headers))
+    } else if (req.url === '/redirect') {
+      res.writeHead(302, { location: `http://localhost:${portOf(target)}/` })
+      res.end()
+    } else {
+      res.end('target')
+    }
+  }))
+  secureTarget = await listen(https.createServer({ key: fixture('server.key'), cert: fixture('server.crt') }, (_req, res) => {
+    res.end('secure target')
+  }))
+})
+
+beforeEach(() => {
+  proxy.seen = []
+})
+
+test('HTTP proxy with auth', async () => {
+  const client = clientWith({
+    http: `http://127.0.0.1:${portOf(proxy)}`,
+    auth: { username: 'user', password: 'pass' },
+  })
+
+  const ret = await client.request(`http://127.0.0.1:${portOf(target)}/`)
+
+  expect(ret.body).toBe('target')
+  expect(ret.headers['x-served-by']).toBe('proxy')
+  expect(proxy.seen).toHaveLength(1)
+  expect(proxy.seen[0].headers['proxy-authorization']).toBe(`Basic ${Buffer.from('user:pass').toString('base64')}`)
+})
+
+test('Proxy headers are sent on CONNECT, never to the origin', async () => {
+  const client = clientWith({
+    all: `http://127.0.0.1:${portOf(proxy)}`,
+    auth: { username: 'user', password: 'pass' },
+    headers: { 'x-proxy-token': 'secret' },
+  })
+
+  const secure = await client.request(`https://localhost:${portOf(secureTarget)}/`)
+  const plain = await client.request(`http://127.0.0.1:${portOf(target)}/headers`)
+
+  expect(secure.body).toBe('secure target')
+  expect(JSON.parse(plain.body)['x-proxy-token']).toBeUndefined()
+  expect(proxy.seen).toHaveLength(2)
+
+  for (const [i, authority] of [`localhost:${portOf(secureTarget)}`, `127.0.0.1:${portOf(target)}`].entries()) {
+    expect(proxy.seen[i].method).toBe('CONNECT')
+    expect(proxy.seen[i].target).toBe(authority)
+    expect(proxy.seen[i].headers['x-proxy-token']).toBe('secret')
+    expect(proxy.seen[i].headers['proxy-authorization']).toBe(`Basic ${Buffer.from('user:pass').toString('base64')}`)
+  }
+})
+
+test('Proxy headers cannot be sent to https:// proxies', () => {
+  expect(() => clientWith({
+    all: 'https://127.0.0.1:8443',
+    headers: { 'x-proxy-token': 'secret' },
+  })).toThrow('Proxy headers can only be sent to http:// proxies')
+})
+
+test('HTTPS requests tunnel through CONNECT', async () => {
+  const client = clientWith({
+    all: `http://127.0.0.1:${portOf(proxy)}`,
+    auth: { username: 'user', password: 'pass' },
+  })
+
+  const ret = await client.request(`https://localhost:${portOf(secureTarget)}/`)
+
+  expect(ret.body).toBe('secure target')
+  expect(proxy.seen).toHaveLength(1)
+  expect(proxy.seen[0].method).toBe('CONNECT')
+  expect(proxy.seen[0].target).toBe(`localhost:${portOf(secureTarget)}`)
+  expect(proxy.seen[0].headers['proxy-authorization']).toBeDefined()
+})
+
+test('Scheme specific proxies', async () => {
+  const client = clientWith({ https: `http://127.0.0.1:${portOf(proxy)}` })
+
+  const ret = await client.request(`http://127.0.0.1:${portOf(target)}/`)
+
+  expect(ret.body).toBe('target')
+  expect(proxy.seen).toHaveLength(0)
+})
+
+describe('Bypass list', () => {
+  const entries = ['127.0.0.1', '127.0.0.0/8', '*', 'localhost']
+
+  for (const entry of entries) {
+    // eslint-disable-next-line @typescript-eslint/no-loop-func
+    test(entry, async () => {
+      const client = clientWith({ all: `http://127.0.0.1:${portOf(proxy)}`, noProxy: [entry] })
+
+      const host = entry === 'localhost' ? 'localhost' : '127.0.0.1'
+      const ret = await client.request(`http://${host}:${portOf(target)}/`)
+
+      expect(ret.body).toBe('target')
+      expect(ret.headers['x-served-by']).toBeUndefined()
+      expect(proxy.seen).toHaveLength(0)
+    })
+  }
+
+  test('Non-matching entries still proxy', async () => {
+    const client = clientWith({ all: `http://127.0.0.1:${portOf(proxy)}`, noProxy: ['*.localhost', '10.0.0.0/8'] })
+
+    const ret = await client.request(`http://127.0.0.1:${portOf(target)}/`)
+
+    expect(ret.headers['x-served-by']).toBe('proxy')
+  })
+})
+
+test('Proxy of the last hop should be reported after redirects', async () => {
+  const client = clientWith({ all: `http://127.0.0.1:${portOf(proxy)}`, noProxy: ['localhost'] })
+
+  const ret = await client.request(`http://127.0.0.1:${portOf(target)}/redirect`)
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBeNull()
+  expect(proxy.seen).toHaveLength(1)
+  expect(proxy.seen[0].target).toBe(`http://127.0.0.1:${portOf(target)}/redirect`)
+})
+
+test('Invalid proxy configuration should throw', () => {
+  expect(() => clientWith({ all: 'ftp://127.0.0.1' })).toThrow('Unsupported proxy scheme')
+  expect(() => clientWith({ all: 'http://127.0.0.1', noProxy: ['10.0.0.0/33'] })).toThrow('Invalid proxy bypass entry')
+})
+
+afterAll(() => {
+  proxy.close()
+  target.close()
+  secureTarget.close()
+})
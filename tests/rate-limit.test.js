@@ -0,0 +1,67 @@
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+let arrivals
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+beforeAll(done => {
+  server = http.createServer((_req, res) => {
+    arrivals.push(Date.now())
+    res.end('ok')
+  })
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+beforeEach(() => {
+  arrivals = []
+})
+
+test('Spaces requests beyond the burst', async () => {
+  const client = clientWith({ rateLimits: [{ origin: '127.0.0.1', requests: 5, interval: 1, burst: 2 }] })
+
+  const url = `http://127.0.0.1:${server.address().port}/`
+
+  await Promise.all(Array.from({ length: 4 }, () => client.request(url)))
+
+  arrivals.sort((a, b) => a - b)
+
+  // The third and fourth requests wait 200ms each.
+  expect(arrivals[3] - arrivals[0]).toBeGreaterThanOrEqual(350)
+})
+
+test('Leaves other origins alone', async () => {
+  const client = clientWith({ rateLimits: [{ origin: 'https://127.0.0.1', requests: 1, interval: 10 }] })
+
+  const url = `http://127.0.0.1:${server.address().port}/`
+
+  const start = Date.now()
+
+  await Promise.all(Array.from({ length: 3 }, () => client.request(url)))
+
+  expect(Date.now() - start).toBeLessThan(1000)
+})
+
+test('Takes a token for every retry', async () => {
+  const client = clientWith({ rateLimits: [{ origin: '*', requests: 1, interval: 1 }] })
+
+  const start = Date.now()
+
+  await expect(client.request('http://127.0.0.1:1/', { attempts: 2 })).rejects.toThrow()
+
+  expect(Date.now() - start).toBeGreaterThanOrEqual(900)
+})
+
+test('Validates limits', () => {
+  expect(() => clientWith({ rateLimits: [{ origin: 'https://example.com/path', requests: 1 }] })).toThrow('Invalid rate limit origin')
+  expect(() => clientWith({ rateLimits: [{ origin: '*', requests: 0 }] })).toThrow('Invalid rate limit requests: 0')
+  expect(() => clientWith({ rateLimits: [{ origin: '*', requests: 1, interval: 0 }] })).toThrow('Invalid rate limit interval: 0')
+  expect(() => clientWith({ rateLimits: [{ origin: '*', requests: 1, burst: 0 }] })).toThrow('Invalid rate limit burst: 0')
+})
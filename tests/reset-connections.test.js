@@ -0,0 +1,134 @@
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+let sockets
+
+const serverUrl = () => `http://127.0.0.1:${server.address().port}/`
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+const closed = socket => new Promise(resolve => (socket.destroyed ? resolve() : socket.once('close', resolve)))
+
+beforeAll(done => {
+  server = http.createServer((req, res) => {
+    if (req.url === '/slow') {
+      setTimeout(() => res.end('slow'), 300)
+    } else {
+      res.end('ok')
+    }
+  })
+  server.on('connection', socket => sockets.push(socket))
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+beforeEach(() => {
+  sockets = []
+})
+
+test('Closes idle connections and opens new ones', async () => {
+  const client = clientWith()
+
+  await client.request(serverUrl())
+  await client.request(serverUrl())
+
+  expect(sockets).toHaveLength(1)
+
+  client.resetConnections()
+
+  await closed(sockets[0])
+
+  await client.request(serverUrl())
+
+  expect(sockets).toHaveLength(2)
+})
+
+test('Lets requests in flight complete', async () => {
+  const client = clientWith()
+
+  const pending = client.request(`${serverUrl()}slow`)
+
+  await new Promise(resolve => server.once('request', resolve))
+
+  client.resetConnections()
+
+  const ret = await pending
+
+  expect(ret.body).toBe('slow')
+
+  await client.request(serverUrl())
+
+  expect(sockets).toHaveLength(2)
+})
+
+test('Still reports new cookies', async () => {
+  const cookieServer = http.createServer((_req, res) => {
+    res.setHeader('Set-Cookie', 'session=1; Max-Age=3600')
+    res.end('ok')
+  })
+
+  await new Promise(resolve => cookieServer.listen(0, '127.0.0.1', resolve))
+
+  const url = `http://127.0.0.1:${cookieServer.address().port}/`
+  const client = clientWith()
+
+  client.resetConnections()
+
+  const ret = await client.request(url)
+
+  expect(Object.values(ret.newCookies)).toEqual([['session=1; Max-Age=3600']])
+
+  cookieServer.close()
+})
+
+test('Lists connection pools per origin', async () => {
+  const client = clientWith()
+
+  expect(client.connections()).toEqual({})
+
+  const origin = serverUrl().slice(0, -1)
+  const pending = client.request(`${serverUrl()}slow`)
+
+  await new Promise(resolve => server.once('request', resolve))
+
+  expect(client.connections()).toEqual({ [origin]: { httpVersion: null, inFlight: 1, idleTime: null } })
+
+  await pending
+
+  const { [origin]: pool } = client.connections()
+
+  expect(pool).toEqual({ httpVersion: 'HTTP/1.1', inFlight: 0, idleTime: expect.any(Number) })
+  expect(pool.idleTime).toBeGreaterThanOrEqual(0)
+})
+
+test('Closes idle connections of one origin', async () => {
+  const other = http.createServer((_req, res) => res.end('other'))
+
+  await new Promise(resolve => other.listen(0, '127.0.0.1', resolve))
+
+  const otherUrl = `http://127.0.0.1:${other.address().port}/`
+  const client = clientWith()
+
+  await client.request(serverUrl())
+  await client.request(otherUrl)
+
+  expect(client.closeIdleConnections(`${serverUrl()}any/path`)).toBe(true)
+
+  await closed(sockets[0])
+
+  expect(Object.keys(client.connections())).toEqual([otherUrl.slice(0, -1)])
+  expect(client.closeIdleConnections(serverUrl())).toBe(false)
+
+  await client.request(serverUrl())
+
+  expect(sockets).toHaveLength(2)
+  expect(() => client.closeIdleConnections('not a url')).toThrow('Invalid URL')
+
+  other.close()
+})
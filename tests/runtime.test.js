@@ -0,0 +1,75 @@
+const fs = require('fs')
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+
+const serverUrl = () => `http://127.0.0.1:${server.address().port}/`
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+const threads = () => fs.readdirSync('/proc/self/task').length
+
+const linuxTest = process.platform === 'linux' ? test : test.skip
+
+beforeAll(done => {
+  server = http.createServer((_req, res) => res.end('ok'))
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+test.each([
+  [{}],
+  [{ runtime: 'shared' }],
+  [{ runtime: 'dedicated' }],
+  [{ runtime: 'dedicated', workerThreads: 1 }],
+  [{ runtime: 'current-thread' }],
+])('Sends requests with %j', async options => {
+  const client = clientWith(options)
+
+  const [a, b] = await Promise.all([client.request(serverUrl()), client.request(serverUrl())])
+
+  expect(a.body).toBe('ok')
+  expect(b.body).toBe('ok')
+})
+
+linuxTest('Shares one runtime between clients', async () => {
+  clientWith()
+
+  const before = threads()
+
+  const clients = Array.from({ length: 4 }, () => clientWith())
+
+  await Promise.all(clients.map(client => client.request(serverUrl())))
+
+  expect(threads()).toBe(before)
+})
+
+linuxTest('Starts the configured worker threads', () => {
+  const before = threads()
+
+  clientWith({ runtime: 'dedicated', workerThreads: 2 })
+
+  expect(threads()).toBe(before + 2)
+})
+
+linuxTest('Runs a current-thread runtime on one thread', () => {
+  const before = threads()
+
+  clientWith({ runtime: 'current-thread' })
+
+  expect(threads()).toBe(before + 1)
+})
+
+test('Validates worker threads', () => {
+  expect(() => clientWith({ workerThreads: 2 })).toThrow('Worker threads can only be set for a dedicated runtime')
+  expect(() => clientWith({ runtime: 'current-thread', workerThreads: 2 })).toThrow('dedicated runtime')
+  expect(() => clientWith({ runtime: 'dedicated', workerThreads: 0 })).toThrow('Invalid worker threads: 0')
+  expect(() => clientWith({ runtime: 'dedicated', workerThreads: 1.5 })).toThrow('Invalid worker threads')
+  expect(() => clientWith({ runtime: 'threaded' })).toThrow('Invalid runtime: threaded')
+})
@@ -0,0 +1,149 @@
+const http = require('http')
+const { Client } = require('../dist')
+const { createSocksServer } = require('./helpers/socks-server')
+
+jest.setTimeout(10e3)
+
+let socks
+let authSocks
+let target
+
+function listen(server, ...host) {
+  return new Promise(resolve => server.listen(0, ...host, () => resolve(server)))
+}
+
+const socksAddress = server => `127.0.0.1:${server.address().port}`
+
+// Reached by hostname, so whether it is resolved locally or by the proxy is visible.
+const targetUrl = () => `http://localhost:${target.address().port}/`
+
+const clientWith = proxy => new Client({ connectTimeout: 5, requestTimeout: 5, proxy })
+
+beforeAll(async () => {
+  socks = await listen(createSocksServer(), '127.0.0.1')
+  authSocks = await listen(createSocksServer({ username: 'user', password: 'secret' }), '127.0.0.1')
+
+  // All interfaces, localhost may resolve to either family.
+  target = await listen(http.createServer((_req, res) => res.end('target')))
+})
+
+afterAll(() => {
+  socks.close()
+  authSocks.close()
+  target.close()
+})
+
+beforeEach(() => {
+  socks.seen = []
+  authSocks.seen = []
+})
+
+test('socks5 resolves hostnames locally', async () => {
+  const ret = await clientWith(`socks5://${socksAddress(socks)}`).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`socks5://${socksAddress(socks)}`)
+  expect(socks.seen).toHaveLength(1)
+  expect(socks.seen[0].type).not.toBe('domain')
+})
+
+test('socks5h resolves hostnames on the proxy', async () => {
+  const ret = await clientWith(`socks5h://${socksAddress(socks)}`).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(socks.seen).toEqual([{ version: 5, type: 'domain', host: 'localhost', port: target.address().port, username: null }])
+})
+
+test('socksRemoteDns resolves socks5 hostnames on the proxy', async () => {
+  const ret = await clientWith({ all: `socks5://${socksAddress(socks)}`, socksRemoteDns: true }).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`socks5h://${socksAddress(socks)}`)
+  expect(socks.seen[0].type).toBe('domain')
+})
+
+test('Authenticates with username and password', async () => {
+  const client = clientWith({
+    all: `socks5h://${socksAddress(authSocks)}`,
+    auth: { username: 'user', password: 'secret' },
+  })
+
+  const ret = await client.request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`socks5h://${socksAddress(authSocks)}`)
+  expect(authSocks.seen[0].username).toBe('user')
+})
+
+test('Authenticates with credentials in the URL', async () => {
+  const ret = await clientWith(`socks5://user:secret@${socksAddress(authSocks)}`).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(authSocks.seen[0].username).toBe('user')
+})
+
+test('Fails with wrong credentials', async () => {
+  const client = clientWith(`socks5h://user:wrong@${socksAddress(authSocks)}`)
+
+  await expect(client.request(targetUrl(), { attempts: 0 })).rejects.toThrow()
+  expect(authSocks.seen).toHaveLength(0)
+})
+
+test('socks4 resolves hostnames locally', async () => {
+  const ret = await clientWith(`socks4://${socksAddress(socks)}`).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`socks4://${socksAddress(socks)}`)
+  expect(socks.seen).toEqual([{ version: 4, type: 'ipv4', host: '127.0.0.1', port: target.address().port, username: null }])
+})
+
+test('socks4a resolves hostnames on the proxy', async () => {
+  const ret = await clientWith(`socks4a://${socksAddress(socks)}`).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(ret.proxy).toBe(`socks4a://${socksAddress(socks)}`)
+  expect(socks.seen).toEqual([{ version: 4, type: 'domain', host: 'localhost', port: target.address().port, username: null }])
+})
+
+test('socks4 sends the user ID', async () => {
+  const ret = await clientWith({ all: `socks4a://${socksAddress(socks)}`, auth: { username: 'user' } }).request(targetUrl())
+
+  expect(ret.body).toBe('target')
+  expect(socks.seen[0].username).toBe('user')
+})
+
+test('socks4 reuses connections through the bridge', async () => {
+  const client = clientWith(`socks4a://${socksAddress(socks)}`)
+
+  await client.request(targetUrl())
+  await client.request(targetUrl())
+
+  expect(socks.seen).toHaveLength(1)
+})
+
+test('Rejects passwords for SOCKS4 proxies', () => {
+  expect(() => clientWith(`socks4://user:secret@${socksAddress(socks)}`)).toThrow('only take a user ID')
+})
+
+test('Client socksRemoteDns applies to proxy URLs', async () => {
+  const client = new Client({ connectTimeout: 5, requestTimeout: 5, proxy: `socks4://${socksAddress(socks)}`, socksRemoteDns: true })
+
+  const ret = await client.request(targetUrl())
+
+  expect(ret.proxy).toBe(`socks4a://${socksAddress(socks)}`)
+  expect(socks.seen[0].type).toBe('domain')
+})
+
+test('Client socksRemoteDns applies to proxy pools', async () => {
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    proxyPool: { proxies: [`socks5://${socksAddress(socks)}`] },
+    socksRemoteDns: true,
+  })
+
+  const ret = await client.request(targetUrl())
+
+  expect(ret.proxy).toBe(`socks5h://${socksAddress(socks)}`)
+  expect(socks.seen).toEqual([expect.objectContaining({ version: 5, type: 'domain', host: 'localhost' })])
+})
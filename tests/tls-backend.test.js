@@ -0,0 +1,38 @@
+const fs = require('fs')
+const path = require('path')
+const https = require('https')
+const { Client, tlsBackend } = require('../dist')
+
+jest.setTimeout(10e3)
+
+const fixture = name => fs.readFileSync(path.join(__dirname, 'fixtures', name))
+
+let server
+
+beforeAll(done => {
+  server = https.createServer({ key: fixture('server.key'), cert: fixture('server.crt') }, (_req, res) => {
+    res.end('ok')
+  })
+
+  server.listen(0, '127.0.0.1', done)
+})
+
+test('Active backend is reported', () => {
+  expect(['native-tls', 'rustls']).toContain(tlsBackend)
+})
+
+test('Handshake with a private root', async () => {
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    rootCertificates: [fixture('ca.crt')],
+  })
+
+  const ret = await client.request(`https://localhost:${server.address().port}`)
+
+  expect(ret.statusCode).toBe(200)
+})
+
+afterAll(() => {
+  server.close()
+})
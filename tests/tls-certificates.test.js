@@ -0,0 +1,92 @@
+const fs = require('fs')
+const path = require('path')
+const https = require('https')
+const { Client, tlsBackend } = require('../dist')
+
+jest.setTimeout(10e3)
+
+const nativeTlsTest = tlsBackend === 'native-tls' ? test : test.skip
+
+const fixture = name => fs.readFileSync(path.join(__dirname, 'fixtures', name))
+
+let server
+let url
+
+beforeAll(done => {
+  server = https.createServer({
+    key: fixture('server.key'),
+    cert: fixture('server.crt'),
+    ca: fixture('ca.crt'),
+    requestCert: true,
+    rejectUnauthorized: true,
+  }, (req, res) => {
+    res.end(req.socket.getPeerCertificate().subject.CN)
+  })
+
+  server.listen(0, '127.0.0.1', () => {
+    url = `https://localhost:${server.address().port}`
+    done()
+  })
+})
+
+test('Untrusted private CA should error', async () => {
+  const client = new Client({ connectTimeout: 5, requestTimeout: 5 })
+
+  await expect(client.request(url, { attempts: 0 })).rejects.toThrow()
+})
+
+test('Missing client certificate should error', async () => {
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    rootCertificates: [fixture('ca.crt')],
+  })
+
+  await expect(client.request(url, { attempts: 0 })).rejects.toThrow()
+})
+
+test('PEM root and PEM identity', async () => {
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    rootCertificates: [fixture('ca.crt').toString()],
+    identity: {
+      cert: fixture('client.crt'),
+      key: fixture('client.key'),
+    },
+  })
+
+  const ret = await client.request(url)
+
+  expect(ret.statusCode).toBe(200)
+  expect(ret.body).toBe('rust-fetch client')
+})
+
+nativeTlsTest('DER root and PKCS#12 identity without built-in roots', async () => {
+  const der = Buffer.from(fixture('ca.crt').toString().replace(/-----[A-Z ]+-----|\s/g, ''), 'base64')
+
+  const client = new Client({
+    connectTimeout: 5,
+    requestTimeout: 5,
+    rootCertificates: [der],
+    tlsBuiltInRootCerts: false,
+    identity: {
+      pkcs12: fixture('client.p12'),
+      password: 'rust-fetch',
+    },
+  })
+
+  const ret = await client.request(url)
+
+  expect(ret.statusCode).toBe(200)
+  expect(ret.body).toBe('rust-fetch client')
+})
+
+test('Invalid root certificate should throw', () => {
+  expect(() => new Client({ rootCertificates: [Buffer.from('nope')] }))
+    .toThrow('Invalid root certificate')
+})
+
+afterAll(() => {
+  server.close()
+})
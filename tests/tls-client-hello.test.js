@@ -0,0 +1,92 @@
+const crypto = require('crypto')
+const { Client, tlsBackend } = require('../dist')
+const { createJa3Server } = require('./helpers/ja3-server')
+
+jest.setTimeout(10e3)
+
+const rustlsTest = tlsBackend === 'rustls' ? test : test.skip
+const nativeTlsTest = tlsBackend === 'native-tls' ? test : test.skip
+
+let server
+let url
+let hellos = []
+
+beforeAll(done => {
+  server = createJa3Server(hello => hellos.push(hello))
+
+  server.listen(0, '127.0.0.1', () => {
+    url = `https://localhost:${server.address().port}`
+    done()
+  })
+})
+
+beforeEach(() => {
+  hellos = []
+})
+
+const clientHello = async options => {
+  const client = new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+  await expect(client.request(url, { attempts: 1 })).rejects.toThrow()
+
+  expect(hellos).toHaveLength(1)
+
+  return hellos.pop()
+}
+
+const chromeOrder = {
+  tlsCipherSuites: [
+    'TLS_AES_128_GCM_SHA256',
+    'TLS_AES_256_GCM_SHA384',
+    'TLS_CHACHA20_POLY1305_SHA256',
+    'TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256',
+    'TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256',
+  ],
+  tlsKeyExchangeGroups: ['X25519', 'secp256r1', 'secp384r1'],
+  tlsSignatureAlgorithms: ['ecdsa_secp256r1_sha256', 'rsa_pss_rsae_sha256', 'rsa_pkcs1_sha256'],
+}
+
+rustlsTest('ClientHello should follow the configured order', async () => {
+  const hello = await clientHello(chromeOrder)
+
+  expect(hello.ciphers).toEqual([0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f])
+  expect(hello.groups).toEqual([0x001d, 0x0017, 0x0018])
+  expect(hello.signatureAlgorithms).toEqual([0x0403, 0x0804, 0x0401])
+})
+
+rustlsTest('JA3 hash should follow the options', async () => {
+  const first = await clientHello(chromeOrder)
+  const second = await clientHello(chromeOrder)
+
+  expect(first.hash).toBe(crypto.createHash('md5').update(first.ja3).digest('hex'))
+  expect(second.hash).toBe(first.hash)
+
+  const reordered = await clientHello({
+    ...chromeOrder,
+    tlsCipherSuites: [...chromeOrder.tlsCipherSuites].reverse(),
+  })
+
+  expect(reordered.hash).not.toBe(first.hash)
+  expect(reordered.ja3.split(',')[1]).toBe('49199-49195-4867-4866-4865')
+})
+
+rustlsTest('Unknown names should throw', () => {
+  expect(() => new Client({ tlsCipherSuites: ['TLS_RSA_WITH_RC4_128_MD5'] })).toThrow('Unsupported cipher suite')
+  expect(() => new Client({ tlsKeyExchangeGroups: ['ffdhe2048'] })).toThrow('Unsupported key exchange group')
+  expect(() => new Client({ tlsSignatureAlgorithms: ['dsa_sha1'] })).toThrow('Unsupported signature algorithm')
+})
+
+rustlsTest('Cipher suites without a usable TLS version should throw', () => {
+  expect(() => new Client({
+    tlsCipherSuites: ['TLS_AES_128_GCM_SHA256'],
+    maxTlsVersion: '1.2',
+  })).toThrow('Invalid TLS configuration')
+})
+
+nativeTlsTest('ClientHello options should throw with native-tls', () => {
+  expect(() => new Client({ tlsCipherSuites: ['TLS_AES_128_GCM_SHA256'] })).toThrow('require the rustls backend')
+})
+
+afterAll(() => {
+  server.close()
+})
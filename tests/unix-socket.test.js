@@ -0,0 +1,206 @@
+const fs = require('fs')
+const os = require('os')
+const path = require('path')
+const http = require('http')
+const zlib = require('zlib')
+const { CookieJar } = require('tough-cookie')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+const unixTest = os.platform() === 'win32' ? test.skip : test
+
+let server
+let socketPath
+let otherServer
+let otherSocketPath
+
+function listenUnix(name) {
+  const file = path.join(fs.mkdtempSync(path.join(os.tmpdir(), 'rust-fetch-')), `${name}.sock`)
+
+  const s = http.createServer((req, res) => {
+    const chunks = []
+
+    req.on('data', chunk => chunks.push(chunk))
+    req.on('end', () => {
+      if (req.url.startsWith('/redirect/')) {
+        const [, , status, location] = req.url.split('/')
+
+        res.writeHead(Number(status), { location: decodeURIComponent(location) })
+        res.end()
+
+        return
+      }
+
+      if (req.url === '/cookie') {
+        res.setHeader('set-cookie', 'session=1; Max-Age=3600')
+      }
+
+      const json = JSON.stringify({
+        server: name,
+        method: req.method,
+        url: req.url,
+        host: req.headers.host,
+        body: Buffer.concat(chunks).toString(),
+        acceptEncoding: req.headers['accept-encoding'],
+        cookie: req.headers.cookie,
+      })
+
+      res.setHeader('content-type', 'application/json')
+
+      if (req.url === '/gzip') {
+        res.setHeader('content-encoding', 'gzip')
+        res.end(zlib.gzipSync(json))
+      } else if (req.url === '/br') {
+        res.setHeader('content-encoding', 'br')
+        res.end(zlib.brotliCompressSync(json))
+      } else {
+        res.end(json)
+      }
+    })
+  })
+
+  return new Promise(resolve => s.listen(file, () => resolve([s, file])))
+}
+
+beforeAll(async () => {
+  if (os.platform() === 'win32') return
+
+  ;[server, socketPath] = await listenUnix('daemon')
+  ;[otherServer, otherSocketPath] = await listenUnix('other')
+})
+
+afterAll(() => {
+  server?.close()
+  otherServer?.close()
+})
+
+unixTest('Sends requests over the client socket', async () => {
+  const client = new Client({ socketPath })
+
+  const ret = await client.request('http://localhost/v1.41/containers/json', { searchParams: { all: 1 } })
+
+  expect(ret.statusCode).toBe(200)
+  expect(ret.headers['content-type']).toBe('application/json')
+  expect(JSON.parse(ret.body)).toEqual({
+    server: 'daemon',
+    method: 'GET',
+    url: '/v1.41/containers/json?all=1',
+    host: 'localhost',
+    body: '',
+    acceptEncoding: 'gzip, br',
+  })
+})
+
+unixTest('Sends bodies and binary responses', async () => {
+  const client = new Client({ socketPath })
+
+  const ret = await client.request('http://daemon:8080/submit', {
+    method: 'POST',
+    body: 'payload',
+    responseType: 'binary',
+  })
+
+  expect(Buffer.isBuffer(ret.body)).toBe(true)
+  expect(JSON.parse(ret.body.toString())).toMatchObject({ method: 'POST', host: 'daemon:8080', body: 'payload' })
+})
+
+unixTest('Uses the socket of the request', async () => {
+  const client = new Client({ socketPath })
+
+  const ret = await client.request('http://localhost/', { socketPath: otherSocketPath })
+
+  expect(JSON.parse(ret.body).server).toBe('other')
+})
+
+unixTest('Keeps connections apart per socket', async () => {
+  const client = new Client()
+
+  const first = await client.request('http://localhost/', { socketPath })
+  const second = await client.request('http://localhost/', { socketPath: otherSocketPath })
+
+  expect(JSON.parse(first.body).server).toBe('daemon')
+  expect(JSON.parse(second.body).server).toBe('other')
+})
+
+unixTest('Fails for missing sockets', async () => {
+  const client = new Client({ socketPath: path.join(os.tmpdir(), 'rust-fetch-missing.sock') })
+
+  await expect(client.request('http://localhost/', { attempts: 0 })).rejects.toThrow('Unix socket request')
+})
+
+unixTest('Decompresses responses', async () => {
+  const client = new Client({ socketPath })
+
+  for (const encoding of ['gzip', 'br']) {
+    const ret = await client.request(`http://localhost/${encoding}`)
+
+    expect(ret.headers['content-encoding']).toBeUndefined()
+    expect(JSON.parse(ret.body).url).toBe(`/${encoding}`)
+  }
+})
+
+unixTest('Reports new cookies', async () => {
+  const client = new Client({ socketPath })
+
+  const ret = await client.request('http://localhost/cookie')
+
+  expect(Object.values(ret.newCookies)).toEqual([['session=1; Max-Age=3600']])
+})
+
+unixTest('Follows redirects within the origin', async () => {
+  const client = new Client({ socketPath })
+
+  const moved = await client.request(`http://localhost/redirect/303/${encodeURIComponent('/moved')}`, {
+    method: 'POST',
+    body: 'payload',
+  })
+
+  expect(moved.statusCode).toBe(200)
+  expect(JSON.parse(moved.body)).toMatchObject({ method: 'GET', url: '/moved', body: '' })
+
+  const kept = await client.request(`http://localhost/redirect/307/${encodeURIComponent('/kept')}`, {
+    method: 'POST',
+    body: 'payload',
+  })
+
+  expect(JSON.parse(kept.body)).toMatchObject({ method: 'POST', url: '/kept', body: 'payload' })
+})
+
+unixTest('Sends the cookies of the jar on every hop', async () => {
+  const cookieJar = new CookieJar()
+  cookieJar.setCookieSync('session=1', 'http://localhost/')
+
+  const client = new Client({ socketPath })
+
+  const ret = await client.request(`http://localhost/redirect/302/${encodeURIComponent('/moved')}`, { cookieJar })
+
+  expect(JSON.parse(ret.body)).toMatchObject({ url: '/moved', cookie: 'session=1' })
+})
+
+unixTest('Returns redirects to other origins and past the limit', async () => {
+  const other = await new Client({ socketPath }).request(`http://localhost/redirect/302/${encodeURIComponent('http://example.com/')}`)
+
+  expect(other.statusCode).toBe(302)
+  expect(other.headers.location).toBe('http://example.com/')
+
+  const unfollowed = await new Client({ socketPath, redirectLimit: 0 }).request(`http://localhost/redirect/302/${encodeURIComponent('/')}`)
+
+  expect(unfollowed.statusCode).toBe(302)
+
+  const twice = `/redirect/302/${encodeURIComponent('/redirect/302/%2F')}`
+  const limited = new Client({ socketPath, redirectLimit: 1 })
+
+  await expect(limited.request(`http://localhost${twice}`)).rejects.toThrow('too many redirects')
+})
+
+unixTest('Is not rate limited', async () => {
+  const client = new Client({ socketPath, rateLimits: [{ origin: '*', requests: 1, interval: 10 }] })
+
+  const start = Date.now()
+
+  await client.request('http://localhost/one')
+  await client.request('http://localhost/two', { socketPath: otherSocketPath })
+
+  expect(Date.now() - start).toBeLessThan(1000)
+})
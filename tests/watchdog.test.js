@@ -0,0 +1,48 @@
+const http = require('http')
+const { Client } = require('../dist')
+
+jest.setTimeout(10e3)
+
+let server
+
+const clientWith = options => new Client({ connectTimeout: 5, requestTimeout: 5, ...options })
+
+beforeAll(done => {
+  server = http.createServer((req, res) => {
+    if (req.url === '/slow') {
+      setTimeout(() => res.end('slow'), 300)
+    } else {
+      res.end('ok')
+    }
+  })
+  server.listen(0, '127.0.0.1', done)
+})
+
+afterAll(() => {
+  server.close()
+})
+
+test('Rejects requests stuck in a DNS lookup', async () => {
+  const client = clientWith({ watchdogTimeout: 0.5, dnsResolver: () => new Promise(() => {}) })
+
+  const start = Date.now()
+
+  await expect(client.request(`http://stuck.test:${server.address().port}/`)).rejects.toMatchObject({
+    code: 'ERR_WATCHDOG_TIMEOUT',
+    message: 'Request did not settle within the watchdog timeout of 500ms',
+  })
+
+  expect(Date.now() - start).toBeLessThan(3000)
+})
+
+test('Leaves requests within the deadline alone', async () => {
+  const client = clientWith({ watchdogTimeout: 2 })
+
+  const ret = await client.request(`http://127.0.0.1:${server.address().port}/slow`)
+
+  expect(ret.body).toBe('slow')
+})
+
+test('Validates the timeout', () => {
+  expect(() => clientWith({ watchdogTimeout: -1 })).toThrow('Invalid watchdog timeout: -1')
+})